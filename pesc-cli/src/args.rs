@@ -25,6 +25,10 @@ pub struct Options {
     pub file: Option<String>,
     pub output: OutputMode,
     pub verbose: bool,
+    pub partial: bool,
+    pub check: bool,
+    pub load: Option<String>,
+    pub save: Option<String>,
 }
 
 impl Options {
@@ -34,9 +38,14 @@ impl Options {
             file: None,
             output: OutputMode::auto(),
             verbose: false,
+            partial: false,
+            check: false,
+            load: None,
+            save: None,
         }
     }
 
+    #[allow(clippy::redundant_pattern_matching)]
     pub fn parse(mut self) -> Result<Self, ()> {
         let args: Vec<String> = env::args().collect();
         let argv0 = args[0].clone();
@@ -47,6 +56,10 @@ impl Options {
         opts.optflag("V", "version",  "");
         opts.optflag("q", "quiet",    "");
         opts.optflag("v", "verbose",  "");
+        opts.optflag("p", "partial",  "");
+        opts.optflag("c", "check",    "");
+        opts.optopt("L",  "load",     "", "FILE");
+        opts.optopt("S",  "save",     "", "FILE");
 
         let matches = match opts.parse(&args[1..]) {
             Ok(ma) => ma,
@@ -71,6 +84,10 @@ impl Options {
         };
 
         self.verbose = matches.opt_present("v");
+        self.partial = matches.opt_present("p");
+        self.check = matches.opt_present("c");
+        self.load = matches.opt_str("L");
+        self.save = matches.opt_str("S");
 
         self.output = {
             if matches.opt_present("q") {
@@ -99,6 +116,13 @@ Options:
     -V, --version          Print pescli's version and exit.
     -v, --verbose          Print extra information.
     -q, --quiet            Print as little information as possible.
+    -p, --partial          Keep the results of code before an error,
+                           rather than undoing the whole line.
+    -c, --check            Look for mistakes in code before running
+                           it, and don't run it if there are any.
+    -L, --load FILE        Restore a session saved with --save.
+    -S, --save FILE        Save the stack, functions, and registers
+                           to FILE on exit.
 
 Full documentation is available as a manpage (pescli(1)).
 Source: https://github.com/lptstr/pesc
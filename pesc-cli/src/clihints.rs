@@ -57,6 +57,7 @@ impl BustyLine {
 }
 
 impl Validator for BustyLine {
+    #[allow(clippy::needless_borrow)]
     fn validate(&self, ctx: &mut ValidationContext)
         -> Result<ValidationResult, ReadlineError>
     {
@@ -65,7 +66,11 @@ impl Validator for BustyLine {
         match Pesc::parse(&input) {
             Ok(_) => Ok(Valid(None)),
             Err(e) => {
-                if let PescErrorType::UnmatchedToken(_) = e.kind {
+                // keep reading lines if something was left open,
+                // but not if there's a stray closing '}' or ')'
+                if let PescErrorType::UnmatchedToken('}' | ')') = e.kind {
+                    Ok(Valid(None))
+                } else if let PescErrorType::UnmatchedToken(_) = e.kind {
                     Ok(Incomplete)
                 } else {
                     Ok(Valid(None))
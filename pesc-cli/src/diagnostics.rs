@@ -0,0 +1,119 @@
+/*
+ * pescli - a CLI Pesc calculator
+ * Copyright (C) 2020 Kiëd Llaentenn <kiedtl [at] protonmail [dot] com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use pesc::errors::*;
+
+use crate::output::OutputMode;
+use crate::tty::{TermStyle, TermColor};
+
+/// Print an error along with the source code it came from, rustc-style:
+///
+///     error: I have no idea what foo means.
+///      --> test.pesc:2:5
+///       |
+///     2 | 1 2 [foo]
+///       |     ^^^^^
+///       = hint: is the function loaded correctly?
+///
+pub fn report(mode: OutputMode, file: &str, src: &str, err: &PescError) {
+    match mode {
+        OutputMode::Human => print!("{}", render(file, src, err, true)),
+        OutputMode::Simple => print!("{}", render(file, src, err, false)),
+        OutputMode::Quiet => match err.span {
+            Some(s) => println!("{}:{}:{}: error: {}", file, s.line, s.col, err),
+            None => println!("{}: error: {}", file, err),
+        },
+    }
+}
+
+fn render(file: &str, src: &str, err: &PescError, color: bool) -> String {
+    let style = |s: TermStyle| if color { s.to_string() } else { String::new() };
+
+    let red   = style(TermStyle::BrightFg(TermColor::Red));
+    let blue  = style(TermStyle::BrightFg(TermColor::Blue));
+    let bold  = style(TermStyle::Bold);
+    let reset = style(TermStyle::Reset);
+
+    let mut buf = format!("{r}{b}error{n}{b}: {e}{n}\n",
+        r = red, b = bold, n = reset, e = err);
+
+    let span = match err.span {
+        Some(s) => s,
+        None => {
+            for hint in err.hints() {
+                buf += &format!("{b}= hint:{n} {h}\n", b = blue, n = reset, h = hint);
+            }
+
+            return buf;
+        },
+    };
+
+    let line = src.lines().nth(span.line - 1).unwrap_or("");
+    let gutter = " ".repeat(span.line.to_string().len());
+
+    // line the carets up with the source line, keeping any tabs so
+    // that they're expanded to the same width
+    let padding = line.chars()
+        .take(span.col - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+
+    // spans over several lines (such as macros) are only underlined
+    // on their first line
+    let width = src.get(span.start..span.end)
+        .unwrap_or("")
+        .chars()
+        .take_while(|&c| c != '\n')
+        .count()
+        .max(1);
+
+    buf += &format!("{g}{b}-->{n} {f}:{l}:{c}\n",
+        g = gutter, b = blue, n = reset, f = file, l = span.line, c = span.col);
+    buf += &format!("{g} {b}|{n}\n", g = gutter, b = blue, n = reset);
+    buf += &format!("{b}{l} |{n} {s}\n", b = blue, n = reset,
+        l = span.line, s = line);
+    buf += &format!("{g} {b}|{n} {p}{r}{u}{n}\n", g = gutter, b = blue,
+        n = reset, p = padding, r = red, u = "^".repeat(width));
+
+    for hint in err.hints() {
+        buf += &format!("{g} {b}= hint:{n} {h}\n", g = gutter, b = blue,
+            n = reset, h = hint);
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pesc::pesc::*;
+
+    #[test]
+    fn test_render() {
+        let src = "1 2 +\n×\t(foo";
+        let err = Pesc::parse(src).unwrap_err();
+
+        assert_eq!(render("test.pesc", src, &err, false), "\
+error: Where's the matching '('?
+ --> test.pesc:2:3
+  |
+2 | ×\t(foo
+  |  \t^^^^
+");
+    }
+}
@@ -18,12 +18,16 @@
 
 mod args;
 mod clihints;
+mod diagnostics;
 mod tty;
 mod output;
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const VERSION: &'static str = "0.1.0";
 
 use pesc::pesc::*;
+use pesc::errors::PescError;
+use pesc::lexer::TokenSpan;
 use pesc::stdlib;
 
 use crate::clihints::*;
@@ -37,9 +41,15 @@ use rustyline::{
         EditMode,
     },
     error::ReadlineError,
+    Cmd,
     Editor,
+    KeyPress,
+    Movement,
 };
 
+// how many lines [undo] can go back in the REPL.
+const HISTORY_SIZE: usize = 64;
+
 fn main() {
     let opts = match Options::new().parse() {
         Ok(o) => o,
@@ -50,32 +60,52 @@ fn main() {
 
     // load standard library
     for func in stdlib::standard() {
-        pesc.load(func.0, func.1, func.2);
+        pesc.load(func.0, func.1, func.2, func.3);
     }
 
     for func in stdlib::extended() {
-        pesc.load(func.0, func.1, func.2);
+        pesc.load(func.0, func.1, func.2, func.3);
+    }
+
+    if let Some(path) = &opts.load {
+        let data = match std::fs::read_to_string(path) {
+            Ok(d) => d,
+            Err(e) => {
+                println!("pescli: error: couldn't read {}: {}", path, e);
+                return;
+            },
+        };
+
+        if let Err(e) = pesc.restore(&data) {
+            diagnostics::report(opts.output, path, &data, &e);
+            return;
+        }
     }
 
     // waitaminute, let's see if there is a file we
     // need execute
-    if let Some(path) = opts.file {
+    if let Some(path) = &opts.file {
         let data = std::fs::read_to_string(path).unwrap();
         let parsed = match Pesc::parse(&data) {
             Ok(r) => r,
             Err(e) => {
-                println!("pesc: error: {}", e);
+                diagnostics::report(opts.output, path, &data, &e);
                 return;
             },
         };
 
-        match pesc.eval(&parsed.1) {
+        if !check(&pesc, &opts, path, &data, &parsed) {
+            return;
+        }
+
+        match eval(&mut pesc, &opts, &parsed) {
             Ok(()) => opts.output.format_stack(&pesc.stack),
             Err((_, e)) => {
-                println!("pesc: error: {}", e);
+                diagnostics::report(opts.output, path, &data, &e);
             },
         }
 
+        save(&pesc, &opts);
         return;
     }
 
@@ -90,6 +120,14 @@ fn main() {
     let mut rl = Editor::<BustyLine>::with_config(config);
     rl.set_helper(Some(BustyLine::new()));
 
+    // Alt-u and Alt-r fill in [undo] and [redo]
+    rl.bind_sequence(KeyPress::Meta('u'),
+        Cmd::Replace(Movement::WholeLine, Some("[undo]".to_string())));
+    rl.bind_sequence(KeyPress::Meta('r'),
+        Cmd::Replace(Movement::WholeLine, Some("[redo]".to_string())));
+
+    pesc.history_limit = HISTORY_SIZE;
+
     loop {
         match rl.readline("pesc> ") {
             Ok(line) => {
@@ -98,15 +136,20 @@ fn main() {
                 let parsed = match Pesc::parse(&line) {
                     Ok(r) => r,
                     Err(e) => {
-                        println!("error: {}", e);
+                        diagnostics::report(opts.output, "<stdin>", &line, &e);
                         continue;
                     },
                 };
 
-                match pesc.eval(&parsed.1) {
+                if !check(&pesc, &opts, "<stdin>", &line, &parsed) {
+                    continue;
+                }
+
+                match eval(&mut pesc, &opts, &parsed) {
                     Ok(()) => (),
-                    Err((_, e)) => {
-                        println!("error: {}", e);
+                    Err((badstack, e)) => {
+                        diagnostics::report(opts.output, "<stdin>", &line, &e);
+                        opts.output.format_failed_stack(&badstack);
                     },
                 }
 
@@ -123,4 +166,45 @@ fn main() {
             Err(_) => opts.output.format_stack(&pesc.stack),
         }
     }
+
+    save(&pesc, &opts);
+}
+
+// save the session, if we were asked to.
+fn save(pesc: &Pesc, opts: &Options) {
+    if let Some(path) = &opts.save {
+        if let Err(e) = std::fs::write(path, pesc.save()) {
+            println!("pescli: error: couldn't save to {}: {}", path, e);
+        }
+    }
+}
+
+// with --check, report any mistakes the checker can find, and whether
+// there weren't any.
+fn check(pesc: &Pesc, opts: &Options, path: &str, src: &str,
+    parsed: &(Vec<TokenSpan>, Vec<PescToken>)) -> bool
+{
+    if !opts.check {
+        return true;
+    }
+
+    let errors = pesc.check(&parsed.1, &parsed.0);
+    for e in &errors {
+        diagnostics::report(opts.output, path, src, e);
+    }
+
+    errors.is_empty()
+}
+
+// unless --partial was given, either all of the code succeeds or none
+// of it does.
+#[allow(clippy::result_large_err)]
+fn eval(pesc: &mut Pesc, opts: &Options, parsed: &(Vec<TokenSpan>, Vec<PescToken>))
+    -> Result<(), (Vec<PescToken>, PescError)>
+{
+    if opts.partial {
+        pesc.eval_spanned(&parsed.1, &parsed.0)
+    } else {
+        pesc.eval_atomic(&parsed.1, &parsed.0)
+    }
 }
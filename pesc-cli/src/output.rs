@@ -25,6 +25,7 @@ use crate::tty::{
 };
 
 const PADDING: usize = 3;
+#[allow(clippy::redundant_static_lifetimes)]
 const MORE_STR: &'static str = " »";
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -54,6 +55,24 @@ impl OutputMode {
         }
     }
 
+    pub fn format_failed_stack(&self, stack: &Vec<PescToken>) {
+        match self {
+            OutputMode::Human => {
+                println!("{g}{i}The stack when it failed:{r}",
+                    g = TermStyle::BrightFg(TermColor::Black),
+                    i = TermStyle::Italic, r = TermStyle::Reset);
+                self.format_stack(stack);
+                println!();
+            },
+            OutputMode::Simple => {
+                println!("failed stack:");
+                self.format_stack(stack);
+            },
+            OutputMode::Quiet => (),
+        }
+    }
+
+    #[allow(clippy::ptr_arg, clippy::len_zero)]
     pub fn format_stack(&self, stack: &Vec<PescToken>) {
         match self {
             OutputMode::Human => {
@@ -75,6 +94,8 @@ impl OutputMode {
                         PescToken::Str(_) => TermStyle::Fg(TermColor::Cyan),
                         PescToken::Number(_) => TermStyle::BrightFg(TermColor::White),
                         PescToken::Macro(_) => TermStyle::Underline,
+                        PescToken::List(_) => TermStyle::Fg(TermColor::Green),
+                        PescToken::Map(_) => TermStyle::Fg(TermColor::Magenta),
                         PescToken::Bool(_) => TermStyle::Fg(TermColor::Yellow),
                         _ => TermStyle::Fg(TermColor::White),
                     };
@@ -41,6 +41,7 @@ pub enum TermStyle {
 }
 
 impl TermStyle {
+    #[allow(noop_method_call)]
     pub fn strip(s: &str) -> String {
         // TODO: cleanup
         let input = s.clone().chars()
@@ -94,11 +95,11 @@ impl fmt::Display for TermStyle {
 #[derive(Copy, Clone, Debug)]
 pub enum TermColor {
     Black,
-    //Red,
-    //Green,
+    Red,
+    Green,
     Yellow,
-    //Blue,
-    //Magenta,
+    Blue,
+    Magenta,
     Cyan,
     White,
 }
@@ -107,11 +108,11 @@ impl fmt::Display for TermColor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         let r = match self {
             TermColor::Black        => "0",
-            //TermColor::Red          => "1",
-            //TermColor::Green        => "2",
+            TermColor::Red          => "1",
+            TermColor::Green        => "2",
             TermColor::Yellow       => "3",
-            //TermColor::Blue         => "4",
-            //TermColor::Magenta      => "5",
+            TermColor::Blue         => "4",
+            TermColor::Magenta      => "5",
             TermColor::Cyan         => "6",
             TermColor::White        => "7",
         };
@@ -131,6 +132,7 @@ pub enum OutputStream {
     Stdin,
 }
 
+#[allow(clippy::from_over_into)]
 impl Into<c_int> for OutputStream {
     fn into(self) -> c_int {
         match self {
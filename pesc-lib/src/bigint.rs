@@ -0,0 +1,754 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::ops::{Add, Mul, Neg, Sub};
+
+// each limb is a base-2^32 digit
+const LIMB_BITS: usize = 32;
+
+// the largest power of ten that fits in a limb, used when
+// converting to and from decimal strings
+const DEC_BASE: u32 = 1_000_000_000;
+const DEC_DIGITS: usize = 9;
+
+/// An arbitrary-precision signed integer.
+///
+/// The magnitude is stored as little-endian base-2^32 limbs without
+/// any trailing zero limbs, so zero is an empty vector (and is never
+/// negative).
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct BigInt {
+    neg: bool,
+    mag: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> Self {
+        Self { neg: false, mag: Vec::new() }
+    }
+
+    pub fn one() -> Self {
+        Self::from(1_u64)
+    }
+
+    fn from_mag(neg: bool, mut mag: Vec<u32>) -> Self {
+        trim(&mut mag);
+        Self { neg: neg && !mag.is_empty(), mag }
+    }
+
+    /// Convert a float to an integer, if (and only if) it is finite
+    /// and has no fractional part.
+    pub fn from_f64(f: f64) -> Option<Self> {
+        if !f.is_finite() || f.fract() != 0.0 {
+            return None;
+        }
+
+        let a = f.abs();
+        let res = if a < 18_446_744_073_709_551_616.0 {
+            Self::from(a as u64)
+        } else {
+            // the float is too big for a u64, so we have to pick
+            // apart the bits:  mantissa × 2^(exponent - 1075)
+            let bits = a.to_bits();
+            let exp = ((bits >> 52) & 0x7ff) as usize;
+            let mant = (bits & ((1 << 52) - 1)) | (1 << 52);
+            Self::from(mant).shl(exp - 1075)
+        };
+
+        Some(if f < 0.0 { -res } else { res })
+    }
+
+    /// Parse a string of decimal digits with an optional leading sign.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (neg, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        // consume the digits in chunks of DEC_DIGITS, with the first
+        // chunk taking up whatever is left over
+        let mut mag = Vec::new();
+        let mut start = 0;
+        let mut end = digits.len() % DEC_DIGITS;
+        if end == 0 {
+            end = DEC_DIGITS;
+        }
+
+        while start < digits.len() {
+            let chunk = digits[start..end].parse::<u32>().ok()?;
+            let scale = 10_u32.pow((end - start) as u32);
+            mul_small_add(&mut mag, scale, chunk);
+
+            start = end;
+            end += DEC_DIGITS;
+        }
+
+        Some(Self::from_mag(neg, mag))
+    }
+
+    /// Parse a string of digits in the given radix (2 to 36), with an
+    /// optional leading sign.
+    pub fn parse_radix(s: &str, radix: u32) -> Option<Self> {
+        let (neg, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut mag = Vec::new();
+        for c in digits.chars() {
+            mul_small_add(&mut mag, radix, c.to_digit(radix)?);
+        }
+
+        Some(Self::from_mag(neg, mag))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.neg
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.mag.first().map(|l| l & 1 == 1).unwrap_or(false)
+    }
+
+    pub fn abs(&self) -> Self {
+        Self { neg: false, mag: self.mag.clone() }
+    }
+
+    /// Convert to the nearest float. Integers too large for an f64
+    /// become infinity.
+    pub fn to_f64(&self) -> f64 {
+        // keep the top 64 bits, and fold everything below them into a
+        // sticky bit so that the conversion from u64 rounds only once.
+        let shift = self.bits().saturating_sub(64);
+        let mut top = self.abs().shr(shift).to_u64().unwrap_or(0);
+
+        let (limbs, rest) = (shift / LIMB_BITS, shift % LIMB_BITS);
+        let sticky = self.mag[..limbs].iter().any(|l| *l != 0)
+            || (rest != 0 && self.mag[limbs] & ((1 << rest) - 1) != 0);
+        if sticky {
+            top |= 1;
+        }
+
+        // scaling by a power of two is exact, short of overflowing
+        let acc = if shift > 1024 {
+            f64::INFINITY
+        } else {
+            top as f64 * 2_f64.powi(shift as i32)
+        };
+
+        if self.neg { -acc } else { acc }
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.neg || self.mag.len() > 2 {
+            return None;
+        }
+
+        Some(self.mag.iter().rev()
+            .fold(0_u64, |acc, l| (acc << LIMB_BITS) | *l as u64))
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        let m = self.abs().to_u64()?;
+        if self.neg {
+            if m <= i64::MAX as u64 + 1 { Some((m as i64).wrapping_neg()) } else { None }
+        } else if m <= i64::MAX as u64 {
+            Some(m as i64)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_usize(&self) -> Option<usize> {
+        self.to_u64().and_then(|v| usize::try_from(v).ok())
+    }
+
+    /// Number of bits in the magnitude, not counting leading zeroes.
+    pub fn bits(&self) -> usize {
+        match self.mag.last() {
+            Some(top) => self.mag.len() * LIMB_BITS - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    /// Number of trailing zero bits in the magnitude (zero for zero).
+    pub fn trailing_zeros(&self) -> usize {
+        let mut tz = 0;
+        for limb in &self.mag {
+            if *limb == 0 {
+                tz += LIMB_BITS;
+            } else {
+                return tz + limb.trailing_zeros() as usize;
+            }
+        }
+
+        0
+    }
+
+    /// Truncating division, returning None if the divisor is zero.
+    /// The remainder takes the sign of the dividend (like Rust's `%`).
+    pub fn divrem(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let (q, r) = mag_divrem(&self.mag, &other.mag);
+        Some((Self::from_mag(self.neg != other.neg, q),
+            Self::from_mag(self.neg, r)))
+    }
+
+    /// Raise to a non-negative power using repeated squaring.
+    pub fn pow(&self, mut exp: u32) -> Self {
+        let mut base = self.clone();
+        let mut acc = Self::one();
+
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = &acc * &base;
+            }
+
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+
+        acc
+    }
+
+    /// Shift the magnitude left by `n` bits, keeping the sign.
+    pub fn shl(&self, n: usize) -> Self {
+        if self.is_zero() {
+            return self.clone();
+        }
+
+        let mut mag = vec![0; n / LIMB_BITS];
+        mag.extend(shl_bits(&self.mag, (n % LIMB_BITS) as u32));
+        Self::from_mag(self.neg, mag)
+    }
+
+    /// Shift the magnitude right by `n` bits, keeping the sign.
+    pub fn shr(&self, n: usize) -> Self {
+        let limbs = n / LIMB_BITS;
+        if limbs >= self.mag.len() {
+            return Self::zero();
+        }
+
+        let mag = shr_bits(&self.mag[limbs..], (n % LIMB_BITS) as u32);
+        Self::from_mag(self.neg, mag)
+    }
+
+    // bitwise operations only make sense for non-negative integers, as
+    // we don't store values in two's complement.
+
+    pub fn and(&self, other: &Self) -> Self {
+        let mag = self.mag.iter().zip(other.mag.iter())
+            .map(|(a, b)| a & b)
+            .collect();
+        Self::from_mag(false, mag)
+    }
+
+    pub fn or(&self, other: &Self) -> Self {
+        Self::from_mag(false, zip_longest(&self.mag, &other.mag, |a, b| a | b))
+    }
+
+    pub fn xor(&self, other: &Self) -> Self {
+        Self::from_mag(false, zip_longest(&self.mag, &other.mag, |a, b| a ^ b))
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(v: u64) -> Self {
+        Self::from_mag(false, vec![v as u32, (v >> LIMB_BITS) as u32])
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(v: i64) -> Self {
+        let m = Self::from(v.unsigned_abs());
+        if v < 0 { -m } else { m }
+    }
+}
+
+impl From<usize> for BigInt {
+    fn from(v: usize) -> Self {
+        Self::from(v as u64)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => mag_cmp(&self.mag, &other.mag),
+            (true, true) => mag_cmp(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        if self.is_zero() {
+            return f.pad_integral(true, "", "0");
+        }
+
+        // peel off DEC_DIGITS digits at a time, least significant first
+        let mut chunks = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = divrem_small(&mag, DEC_BASE);
+            chunks.push(r);
+            mag = q;
+        }
+
+        let mut buf = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            buf += &format!("{:01$}", chunk, DEC_DIGITS);
+        }
+
+        f.pad_integral(!self.neg, "", &buf)
+    }
+}
+
+// the LowerHex, Octal, and Binary impls, which (like the ones for
+// the primitive integers) add a prefix when asked with "{:#x}".
+macro_rules! fmt_radix {
+    ($trait:ident, $radix:expr, $prefix:expr) => {
+        impl fmt::$trait for BigInt {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+                let mut digits = Vec::new();
+                let mut mag = self.mag.clone();
+                while !mag.is_empty() {
+                    let (q, r) = divrem_small(&mag, $radix);
+                    digits.extend(std::char::from_digit(r, $radix));
+                    mag = q;
+                }
+
+                if digits.is_empty() {
+                    digits.push('0');
+                }
+
+                let buf = digits.iter().rev().collect::<String>();
+                f.pad_integral(!self.neg, $prefix, &buf)
+            }
+        }
+    };
+}
+
+fmt_radix!(LowerHex, 16, "0x");
+fmt_radix!(Octal, 8, "0o");
+fmt_radix!(Binary, 2, "0b");
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(mut self) -> BigInt {
+        if !self.is_zero() {
+            self.neg = !self.neg;
+        }
+
+        self
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        -self.clone()
+    }
+}
+
+impl<'a> Add<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.neg == other.neg {
+            return BigInt::from_mag(self.neg, mag_add(&self.mag, &other.mag));
+        }
+
+        // different signs, so subtract the smaller magnitude from
+        // the larger one and take the sign of the larger
+        match mag_cmp(&self.mag, &other.mag) {
+            Ordering::Equal => BigInt::zero(),
+            Ordering::Greater =>
+                BigInt::from_mag(self.neg, mag_sub(&self.mag, &other.mag)),
+            Ordering::Less =>
+                BigInt::from_mag(other.neg, mag_sub(&other.mag, &self.mag)),
+        }
+    }
+}
+
+impl<'a> Sub<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &(-other)
+    }
+}
+
+impl<'a> Mul<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_mag(self.neg != other.neg, mag_mul(&self.mag, &other.mag))
+    }
+}
+
+impl Add for BigInt {
+    type Output = BigInt;
+    fn add(self, other: BigInt) -> BigInt { &self + &other }
+}
+
+impl Sub for BigInt {
+    type Output = BigInt;
+    fn sub(self, other: BigInt) -> BigInt { &self - &other }
+}
+
+impl Mul for BigInt {
+    type Output = BigInt;
+    fn mul(self, other: BigInt) -> BigInt { &self * &other }
+}
+
+// --- magnitude helpers ---
+
+fn trim(v: &mut Vec<u32>) {
+    while let Some(&0) = v.last() {
+        v.pop();
+    }
+}
+
+fn mag_cmp(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+
+    Ordering::Equal
+}
+
+fn zip_longest<F>(a: &[u32], b: &[u32], f: F) -> Vec<u32>
+where
+    F: Fn(u32, u32) -> u32
+{
+    (0..a.len().max(b.len()))
+        .map(|i| f(*a.get(i).unwrap_or(&0), *b.get(i).unwrap_or(&0)))
+        .collect()
+}
+
+fn mag_add(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0_u64;
+
+    for i in 0..a.len().max(b.len()) {
+        let s = *a.get(i).unwrap_or(&0) as u64
+            + *b.get(i).unwrap_or(&0) as u64 + carry;
+        res.push(s as u32);
+        carry = s >> LIMB_BITS;
+    }
+
+    if carry > 0 {
+        res.push(carry as u32);
+    }
+
+    res
+}
+
+// assumes a >= b
+fn mag_sub(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0_i64;
+
+    for (i, x) in a.iter().enumerate() {
+        let mut d = *x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = if d < 0 { d += 1 << LIMB_BITS; 1 } else { 0 };
+        res.push(d as u32);
+    }
+
+    trim(&mut res);
+    res
+}
+
+fn mag_mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+
+    // plain old schoolbook multiplication
+    let mut res = vec![0_u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0_u64;
+        for (j, y) in b.iter().enumerate() {
+            let t = *x as u64 * *y as u64 + res[i + j] as u64 + carry;
+            res[i + j] = t as u32;
+            carry = t >> LIMB_BITS;
+        }
+
+        res[i + b.len()] = carry as u32;
+    }
+
+    trim(&mut res);
+    res
+}
+
+// v = v * m + a
+fn mul_small_add(v: &mut Vec<u32>, m: u32, a: u32) {
+    let mut carry = a as u64;
+    for limb in v.iter_mut() {
+        let t = *limb as u64 * m as u64 + carry;
+        *limb = t as u32;
+        carry = t >> LIMB_BITS;
+    }
+
+    if carry > 0 {
+        v.push(carry as u32);
+    }
+}
+
+fn divrem_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut q = vec![0_u32; a.len()];
+    let mut rem = 0_u64;
+
+    for i in (0..a.len()).rev() {
+        let cur = (rem << LIMB_BITS) | a[i] as u64;
+        q[i] = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    }
+
+    trim(&mut q);
+    (q, rem as u32)
+}
+
+fn shl_bits(a: &[u32], s: u32) -> Vec<u32> {
+    if s == 0 {
+        return a.to_vec();
+    }
+
+    let mut res = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0_u32;
+    for limb in a {
+        res.push((limb << s) | carry);
+        carry = limb >> (LIMB_BITS as u32 - s);
+    }
+
+    if carry > 0 {
+        res.push(carry);
+    }
+
+    res
+}
+
+fn shr_bits(a: &[u32], s: u32) -> Vec<u32> {
+    let mut res = a.to_vec();
+    if s > 0 {
+        for i in 0..res.len() {
+            let hi = a.get(i + 1).map(|l| l << (LIMB_BITS as u32 - s)).unwrap_or(0);
+            res[i] = (a[i] >> s) | hi;
+        }
+    }
+
+    trim(&mut res);
+    res
+}
+
+// Knuth's Algorithm D (TAOCP vol. 2, 4.3.1), more or less as presented
+// in Hacker's Delight.
+fn mag_divrem(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if mag_cmp(u, v) == Ordering::Less {
+        return (Vec::new(), u.to_vec());
+    }
+
+    if v.len() == 1 {
+        let (q, r) = divrem_small(u, v[0]);
+        let mut r = vec![r];
+        trim(&mut r);
+        return (q, r);
+    }
+
+    const B: u64 = 1 << LIMB_BITS;
+
+    // normalize, so that the top limb of the divisor has its high
+    // bit set
+    let s = v[v.len() - 1].leading_zeros();
+    let vn = shl_bits(v, s);
+    let mut un = shl_bits(u, s);
+    un.resize(u.len() + 1, 0);
+
+    let n = vn.len();
+    let m = u.len() - n;
+    let mut q = vec![0_u32; m + 1];
+
+    for j in (0..=m).rev() {
+        // estimate the quotient digit...
+        let num = ((un[j + n] as u64) << LIMB_BITS) | un[j + n - 1] as u64;
+        let mut qhat = num / vn[n - 1] as u64;
+        let mut rhat = num % vn[n - 1] as u64;
+
+        // ...and refine it, so that it's at most one too large
+        while qhat >= B || qhat * vn[n - 2] as u64
+            > ((rhat << LIMB_BITS) | un[j + n - 2] as u64)
+        {
+            qhat -= 1;
+            rhat += vn[n - 1] as u64;
+            if rhat >= B {
+                break;
+            }
+        }
+
+        // multiply and subtract
+        let mut k = 0_i64;
+        for i in 0..n {
+            let p = qhat * vn[i] as u64;
+            let t = un[i + j] as i64 - k - (p & 0xffff_ffff) as i64;
+            un[i + j] = t as u32;
+            k = (p >> LIMB_BITS) as i64 - (t >> LIMB_BITS);
+        }
+
+        let t = un[j + n] as i64 - k;
+        un[j + n] = t as u32;
+
+        // we subtracted too much, add one divisor back
+        if t < 0 {
+            qhat -= 1;
+            let mut carry = 0_u64;
+            for i in 0..n {
+                let s = un[i + j] as u64 + vn[i] as u64 + carry;
+                un[i + j] = s as u32;
+                carry = s >> LIMB_BITS;
+            }
+
+            un[j + n] = un[j + n].wrapping_add(carry as u32);
+        }
+
+        q[j] = qhat as u32;
+    }
+
+    trim(&mut q);
+    (q, shr_bits(&un[..n], s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        BigInt::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_display() {
+        for s in &["0", "1", "-1", "4294967296", "-18446744073709551616",
+            "1000000000", "999999999999999999999999999999",
+            "15511210043330985984000000"] {
+            assert_eq!(&big(s).to_string(), s);
+        }
+
+        assert_eq!(big("-0"), BigInt::zero());
+        assert_eq!(big("+007").to_string(), "7");
+        assert!(BigInt::parse("").is_none());
+        assert!(BigInt::parse("-").is_none());
+        assert!(BigInt::parse("1.5").is_none());
+
+        assert_eq!(BigInt::parse_radix("ff", 16), Some(big("255")));
+        assert_eq!(BigInt::parse_radix("-101", 2), Some(big("-5")));
+        assert_eq!(BigInt::parse_radix("ffffffffffffffffffffffff", 16),
+            Some(big("79228162514264337593543950335")));
+        assert!(BigInt::parse_radix("12", 2).is_none());
+        assert!(BigInt::parse_radix("-", 8).is_none());
+
+        assert_eq!(format!("{:#x}", big("-79228162514264337593543950335")),
+            "-0xffffffffffffffffffffffff");
+        assert_eq!(format!("{:o} {:#b} {:x}", big("15"), big("5"), BigInt::zero()), "17 0b101 0");
+    }
+
+    #[test]
+    fn test_arith() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+
+        assert_eq!((&a + &b).to_string(), "-864197532086419753208641975320");
+        assert_eq!((&a - &b).to_string(), "1111111110111111111011111111100");
+        assert_eq!((&a * &b).to_string(),
+            "-121932631137021795226185032733622923332237463801111263526900");
+        assert_eq!(&(&a - &a), &BigInt::zero());
+        assert_eq!(big("2").pow(100).to_string(), "1267650600228229401496703205376");
+    }
+
+    #[test]
+    fn test_divrem() {
+        let a = big("1267650600228229401496703205376");
+        let b = big("1000000000000000000000");
+        let (q, r) = a.divrem(&b).unwrap();
+        assert_eq!(q.to_string(), "1267650600");
+        assert_eq!(r.to_string(), "228229401496703205376");
+
+        let (q, r) = big("-7").divrem(&big("2")).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), ("-3".into(), "-1".into()));
+
+        // check (a / b) * b + (a % b) == a for some nasty values
+        let a = big("340282366920938463463374607431768211455");
+        let b = big("18446744073709551617");
+        let (q, r) = a.divrem(&b).unwrap();
+        assert_eq!(&(&q * &b) + &r, a);
+        assert!(r < b);
+
+        assert!(a.divrem(&BigInt::zero()).is_none());
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(BigInt::from_f64(1e20).unwrap().to_string(), "100000000000000000000");
+        assert_eq!(BigInt::from_f64(-3.0).unwrap(), BigInt::from(-3_i64));
+        assert!(BigInt::from_f64(0.5).is_none());
+        assert!(BigInt::from_f64(f64::NAN).is_none());
+        assert_eq!(big("1267650600228229401496703205376").to_f64(), 2_f64.powi(100));
+
+        // 2^90 + 2^37 + 1 is just above a tie, which rounding twice
+        // (once the top limbs fill a mantissa, then again at the end)
+        // would settle downwards
+        let up = 2_f64.powi(90) + 2_f64.powi(38);
+        assert_eq!(big("1237940039285380412338077697").to_f64(), up);
+        assert_eq!(big("-1237940039285380412338077697").to_f64(), -up);
+        // while exact ties round to even
+        assert_eq!(big("1237940039285380412338077696").to_f64(), 2_f64.powi(90));
+        assert_eq!(big("1237940039285380687215984640").to_f64(), 2_f64.powi(90) + 2_f64.powi(39));
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(big("18446744073709551616").to_u64(), None);
+    }
+
+    #[test]
+    fn test_bits() {
+        assert_eq!(big("1").shl(100), big("2").pow(100));
+        assert_eq!(big("2").pow(100).shr(99), big("2"));
+        assert_eq!(big("12").and(&big("10")), big("8"));
+        assert_eq!(big("12").or(&big("3")), big("15"));
+        assert_eq!(big("12").xor(&big("10")), big("6"));
+        assert_eq!(big("40").trailing_zeros(), 3);
+    }
+}
@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+use crate::pesc::PescToken;
+
+/// How to execute a single token of a compiled chunk.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Op {
+    /// Push a copy of the token onto the stack.
+    Push,
+
+    /// Call the function in the given slot.
+    Call(usize),
+
+    /// The token is an operator that wasn't loaded. Executing this is
+    /// an error.
+    Unknown,
+}
+
+/// Some Pesc code that's been compiled with Pesc::compile, with the
+/// functions it calls already looked up.
+///
+/// `ops[i]` says how to execute `code[i]`, so the original tokens are
+/// always at hand for error messages and for printing the code back
+/// out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<PescToken>,
+    pub ops: Vec<Op>,
+}
+
+impl Chunk {
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// The tokens of a macro value, along with the chunk they were last
+/// compiled to, so that a macro called over and over (e.g. by [times]
+/// or [while]) is only compiled once.
+///
+/// Copies of a macro share the same compiled chunk. It's tagged with
+/// the Pesc::stamp it was compiled under, since another interpreter (or
+/// the same one, after an operator was loaded) would compile it
+/// differently.
+#[derive(Clone, Default)]
+pub struct Macro(Rc<MacroBody>);
+
+#[derive(Default)]
+struct MacroBody {
+    code: Vec<PescToken>,
+    chunk: RefCell<Option<(usize, Rc<Chunk>)>>,
+}
+
+impl Macro {
+    pub fn new(code: Vec<PescToken>) -> Self {
+        Self(Rc::new(MacroBody { code, chunk: RefCell::new(None) }))
+    }
+
+    /// Take the tokens of the macro, copying them if the macro is
+    /// shared.
+    pub fn into_code(self) -> Vec<PescToken> {
+        match Rc::try_unwrap(self.0) {
+            Ok(body) => body.code,
+            Err(shared) => shared.code.clone(),
+        }
+    }
+
+    // the chunk this was compiled to under the given stamp, if any.
+    pub(crate) fn compiled(&self, stamp: usize) -> Option<Rc<Chunk>> {
+        match &*self.0.chunk.borrow() {
+            Some((s, chunk)) if *s == stamp => Some(chunk.clone()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn set_compiled(&self, stamp: usize, chunk: Rc<Chunk>) {
+        *self.0.chunk.borrow_mut() = Some((stamp, chunk));
+    }
+}
+
+impl Deref for Macro {
+    type Target = [PescToken];
+
+    fn deref(&self) -> &[PescToken] {
+        &self.0.code
+    }
+}
+
+impl From<Vec<PescToken>> for Macro {
+    fn from(code: Vec<PescToken>) -> Self {
+        Self::new(code)
+    }
+}
+
+impl PartialEq for Macro {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.code == other.0.code
+    }
+}
+
+impl fmt::Debug for Macro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.code.fmt(f)
+    }
+}
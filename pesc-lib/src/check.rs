@@ -0,0 +1,622 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::mem;
+use crate::errors::*;
+use crate::lexer::{Span, TokenSpan};
+use crate::number::PescNumber;
+use crate::pesc::*;
+
+// how deeply we follow literal macros into [run], [if?], and [def],
+// so that a macro that runs itself doesn't send us in circles
+const MAX_DEPTH: usize = 32;
+
+/// The type of a value in a stack-effect signature.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    Any,
+    Number,
+    Str,
+    Bool,
+    Macro,
+    List,
+    Map,
+
+    /// A function or operator token.
+    Func,
+
+    /// A macro or function; anything that [run] accepts.
+    Callable,
+
+    /// A macro, list, or string; anything the combinators walk over.
+    Seq,
+
+    /// Stands for the same type wherever it appears in a signature, as
+    /// in "a -- a a" for [dup].
+    Var(char),
+}
+
+impl Type {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "any"      => Type::Any,
+            "number"   => Type::Number,
+            "string"   => Type::Str,
+            "bool"     => Type::Bool,
+            "macro"    => Type::Macro,
+            "list"     => Type::List,
+            "map"      => Type::Map,
+            "function" => Type::Func,
+            "callable" => Type::Callable,
+            "seq"      => Type::Seq,
+            _ if s.len() == 1 && s.bytes().all(|b| b.is_ascii_lowercase()) =>
+                Type::Var(s.chars().next()?),
+            _ => return None,
+        })
+    }
+
+    /// The type of a token.
+    pub fn of(t: &PescToken) -> Self {
+        match t {
+            PescToken::Number(_) => Type::Number,
+            PescToken::Str(_) => Type::Str,
+            PescToken::Bool(_) => Type::Bool,
+            PescToken::Macro(_) => Type::Macro,
+            PescToken::List(_) => Type::List,
+            PescToken::Map(_) => Type::Map,
+            PescToken::Func(_)
+            | PescToken::Symbol(_) => Type::Func,
+        }
+    }
+
+    /// Whether a value of type `found` can be given where this type
+    /// is wanted. Strings and numbers can be used as booleans.
+    pub fn accepts(&self, found: Type) -> bool {
+        match (*self, found) {
+            (Type::Any, _) | (Type::Var(_), _) | (_, Type::Any) => true,
+            (Type::Bool, Type::Number) | (Type::Bool, Type::Str) => true,
+            (Type::Callable, Type::Macro) | (Type::Callable, Type::Func) => true,
+            (Type::Seq, Type::Macro) | (Type::Seq, Type::List)
+            | (Type::Seq, Type::Str) => true,
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Type::Any      => write!(f, "any"),
+            Type::Number   => write!(f, "number"),
+            Type::Str      => write!(f, "string"),
+            Type::Bool     => write!(f, "bool"),
+            Type::Macro    => write!(f, "macro"),
+            Type::List     => write!(f, "list"),
+            Type::Map      => write!(f, "map"),
+            Type::Func     => write!(f, "function"),
+            Type::Callable => write!(f, "callable"),
+            Type::Seq      => write!(f, "seq"),
+            Type::Var(c)   => write!(f, "{}", c),
+        }
+    }
+}
+
+/// What a function takes from the stack and what it leaves there,
+/// written like a Forth stack comment: "number number -- number". Both
+/// sides are listed deepest first, and inputs may be given names, as in
+/// "a:number b:number -- number". A "..." means that the function
+/// takes or leaves values that can't be known ahead of time, as [run]
+/// does.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub inputs: Vec<Type>,
+    pub outputs: Vec<Type>,
+    pub more_inputs: bool,
+    pub more_outputs: bool,
+}
+
+impl Signature {
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut sides = s.splitn(2, "--");
+        let (inputs, more_inputs) = Self::side(sides.next()?)?;
+        let (outputs, more_outputs) = Self::side(sides.next()?)?;
+
+        Some(Self { inputs, outputs, more_inputs, more_outputs })
+    }
+
+    fn side(s: &str) -> Option<(Vec<Type>, bool)> {
+        let mut types = Vec::new();
+        let mut more = false;
+
+        for word in s.split_whitespace() {
+            match word {
+                "..." => more = true,
+                _ => types.push(Type::parse(word.rsplit(':').next()?)?),
+            }
+        }
+
+        Some((types, more))
+    }
+
+    /// A signature for a function that could do anything.
+    pub fn unknown() -> Self {
+        Self {
+            inputs: Vec::new(), outputs: Vec::new(),
+            more_inputs: true, more_outputs: true,
+        }
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let mut words = Vec::new();
+
+        if self.more_inputs {
+            words.push(String::from("..."));
+        }
+        words.extend(self.inputs.iter().map(|t| t.to_string()));
+        words.push(String::from("--"));
+        words.extend(self.outputs.iter().map(|t| t.to_string()));
+        if self.more_outputs {
+            words.push(String::from("..."));
+        }
+
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Look for stack underflows, type mismatches, and calls to unknown
+/// functions in some code, without running it. Checking starts from
+/// the current contents of the stack.
+///
+/// Only what can be known ahead of time is checked. Once the code
+/// does something unpredictable (such as running a macro that isn't
+/// written out in the code, or calling a function whose signature
+/// isn't known), the checker stops complaining about underflows until
+/// it can be sure of the stack again, which is usually never.
+pub fn check(pesc: &Pesc, code: &[PescToken], spans: &[TokenSpan]) -> Vec<PescError> {
+    let mut checker = Checker {
+        pesc,
+        state: State::default(),
+        infer: false,
+        defs: HashMap::new(),
+        defs_unknown: false,
+        errors: Vec::new(),
+        depth: 0,
+    };
+
+    checker.state.stack = pesc.stack.iter()
+        .map(|t| Value::literal(t, Vec::new()))
+        .collect();
+    checker.block(code, spans, None);
+
+    // the same macro might have been checked several times
+    let mut errors: Vec<PescError> = Vec::new();
+    for e in checker.errors {
+        if !errors.iter().any(|o| o.span == e.span && o.kind == e.kind) {
+            errors.push(e);
+        }
+    }
+
+    errors
+}
+
+// what we know about a value on the stack.
+#[derive(Clone, Debug)]
+struct Value {
+    ty: Type,
+
+    // the value itself, if it came from a literal, along with the
+    // spans of its contents if it's a macro
+    lit: Option<PescToken>,
+    spans: Vec<TokenSpan>,
+}
+
+impl Value {
+    fn literal(t: &PescToken, spans: Vec<TokenSpan>) -> Self {
+        Self { ty: Type::of(t), lit: Some(t.clone()), spans }
+    }
+
+    fn of(ty: Type) -> Self {
+        let ty = match ty {
+            // we don't know which of these it'll be
+            Type::Callable | Type::Seq | Type::Var(_) => Type::Any,
+            t => t,
+        };
+
+        Self { ty, lit: None, spans: Vec::new() }
+    }
+
+    fn any() -> Self {
+        Self::of(Type::Any)
+    }
+
+    fn index(&self) -> Option<usize> {
+        match &self.lit {
+            Some(PescToken::Number(n)) => n.to_usize(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct State {
+    stack: Vec<Value>,
+
+    // whether we've lost track of what's below `stack`
+    lost: bool,
+
+    // when inferring a signature, the inputs taken from below `stack`
+    taken: Vec<Type>,
+}
+
+struct Checker<'a> {
+    pesc: &'a Pesc,
+    state: State,
+
+    // whether popping from an empty stack takes an input (when
+    // inferring the signature of a macro), rather than underflowing
+    infer: bool,
+
+    // signatures of functions defined by the code being checked (None
+    // if it couldn't be worked out), and whether something was
+    // defined under a name we couldn't see
+    defs: HashMap<String, Option<Signature>>,
+    defs_unknown: bool,
+
+    errors: Vec<PescError>,
+    depth: usize,
+}
+
+impl<'a> Checker<'a> {
+    fn block(&mut self, code: &[PescToken], spans: &[TokenSpan], fallback: Option<Span>) {
+        for (i, tok) in code.iter().enumerate() {
+            let tspan = spans.get(i);
+            let span = tspan.map(|s| s.span).or(fallback);
+
+            match tok {
+                PescToken::Func(f) => self.call(f, span),
+                PescToken::Symbol(c) => self.symbol(*c, span),
+                _ => {
+                    let inner = tspan.map(|s| s.inner.clone()).unwrap_or_default();
+                    self.state.stack.push(Value::literal(tok, inner));
+                },
+            }
+        }
+    }
+
+    fn error(&mut self, span: Option<Span>, kind: PescErrorType) {
+        self.errors.push(PescError::new(span, None, kind));
+    }
+
+    // forget everything we know about the stack.
+    fn lose(&mut self) {
+        self.state.stack.clear();
+        self.state.lost = true;
+    }
+
+    fn pop(&mut self, want: Type, span: Option<Span>) -> Value {
+        let v = match self.state.stack.pop() {
+            Some(v) => v,
+            None if self.state.lost => Value::any(),
+            None if self.infer => {
+                self.state.taken.insert(0, Value::of(want).ty);
+                Value::any()
+            },
+            None => {
+                self.error(span, PescErrorType::NotEnoughArguments(None));
+                self.lose();
+                Value::any()
+            },
+        };
+
+        if !want.accepts(v.ty) {
+            self.error(span, PescErrorType::InvalidArgumentType(
+                want.to_string(), v.ty.to_string()));
+        }
+
+        v
+    }
+
+    // report it if a function is given fewer than `needed` arguments.
+    // the values that are there are still checked.
+    fn arity(&mut self, fnname: &str, needed: usize, span: Option<Span>) {
+        let given = self.state.stack.len();
+
+        if given < needed && !self.state.lost && !self.infer {
+            self.error(span, PescErrorType::NotEnoughArguments(
+                Some((fnname.to_string(), needed, given))));
+            self.state.lost = true;
+        }
+    }
+
+    fn apply(&mut self, fnname: &str, sig: &Signature, span: Option<Span>) {
+        let mut vars: HashMap<char, Value> = HashMap::new();
+
+        if !sig.more_inputs {
+            self.arity(fnname, sig.inputs.len(), span);
+        }
+
+        for want in sig.inputs.iter().rev() {
+            let v = self.pop(*want, span);
+            if let Type::Var(c) = want {
+                vars.entry(*c).or_insert(v);
+            }
+        }
+
+        if sig.more_inputs || sig.more_outputs {
+            self.lose();
+        }
+
+        for out in &sig.outputs {
+            let v = match out {
+                Type::Var(c) => vars.get(c).cloned().unwrap_or_else(Value::any),
+                t => Value::of(*t),
+            };
+            self.state.stack.push(v);
+        }
+    }
+
+    fn symbol(&mut self, c: char, span: Option<Span>) {
+        match self.pesc.ops.get(&c) {
+            Some(f) => self.call(f, span),
+            None => {
+                self.error(span, PescErrorType::UnknownFunction(c.to_string()));
+                self.lose();
+            },
+        }
+    }
+
+    fn call(&mut self, fnname: &str, span: Option<Span>) {
+        let name = fnname.to_lowercase();
+
+        if let Some(sig) = self.defs.get(&name) {
+            let sig = sig.clone().unwrap_or_else(Signature::unknown);
+            return self.apply(&name, &sig, span);
+        }
+
+        let chunk = match self.pesc.func(&name) {
+            Some(PescFuncBody::Native(_)) => return self.native(&name, span),
+            Some(PescFuncBody::Macro(chunk)) => chunk.clone(),
+            None if self.defs_unknown => return self.lose(),
+            None => {
+                self.error(span, PescErrorType::UnknownFunction(fnname.to_string()));
+                return self.lose();
+            },
+        };
+
+        // a function defined before the code was checked; its own
+        // problems aren't the fault of this code
+        self.defs.insert(name.clone(), None);
+        let sig = self.infer(&chunk.code, &[], span, false);
+        self.defs.insert(name.clone(), sig.clone());
+        self.apply(&name, &sig.unwrap_or_else(Signature::unknown), span);
+    }
+
+    fn native(&mut self, name: &str, span: Option<Span>) {
+        // the stdlib functions whose effects depend on their arguments
+        match name {
+            "run" => {
+                self.arity(name, 1, span);
+                let f = self.pop(Type::Callable, span);
+                self.run(f, span);
+            },
+            "if?" => {
+                self.arity(name, 3, span);
+                self.pop(Type::Bool, span);
+                let main = self.pop(Type::Callable, span);
+                let other = self.pop(Type::Callable, span);
+                self.branch(main, other, span);
+            },
+            "def" => {
+                self.arity(name, 2, span);
+                let name = self.pop(Type::Str, span);
+                let body = self.pop(Type::Macro, span);
+                self.define(name, body, span);
+            },
+            "get" => {
+                self.arity(name, 1, span);
+                let i = self.pop(Type::Number, span).index();
+                let v = match self.nth(i, span) {
+                    Some(n) => self.state.stack[n].clone(),
+                    None => Value::any(),
+                };
+                self.state.stack.push(v);
+            },
+            "rot" => {
+                self.arity(name, 1, span);
+                let i = self.pop(Type::Number, span).index();
+                match (self.nth(i, span), self.state.stack.len()) {
+                    (Some(n), len) => self.state.stack.swap(n, len - 1),
+                    (None, _) => {
+                        // something was swapped with the top, but we
+                        // don't know what
+                        for v in &mut self.state.stack {
+                            *v = Value::any();
+                        }
+                    },
+                }
+            },
+            _ => {
+                let sig = self.pesc.info(name).map(|i| i.sig.clone())
+                    .unwrap_or_else(Signature::unknown);
+                self.apply(name, &sig, span);
+            },
+        }
+    }
+
+    // find the nth value from the top of the stack, for [get] and
+    // [rot], reporting it if it's out of bounds.
+    fn nth(&mut self, i: Option<usize>, span: Option<Span>) -> Option<usize> {
+        let len = self.state.stack.len();
+
+        match i {
+            Some(i) if i < len => Some(len - 1 - i),
+            Some(_) | None if self.state.lost || self.infer => {
+                // an input we can't see, in the case of inference
+                if self.infer {
+                    self.lose();
+                }
+                None
+            },
+            Some(i) => {
+                self.error(span, PescErrorType::OutOfBounds(PescNumber::from(i), len));
+                None
+            },
+            None => None,
+        }
+    }
+
+    fn run(&mut self, f: Value, span: Option<Span>) {
+        match f.lit {
+            Some(PescToken::Macro(body)) if self.depth < MAX_DEPTH => {
+                self.depth += 1;
+                self.block(&body, &f.spans, span);
+                self.depth -= 1;
+            },
+            Some(PescToken::Func(name)) => self.call(&name, span),
+            Some(PescToken::Symbol(c)) => self.symbol(c, span),
+            _ => self.lose(),
+        }
+    }
+
+    // run either of two macros, keeping whatever both agree on.
+    fn branch(&mut self, main: Value, other: Value, span: Option<Span>) {
+        let before = self.state.clone();
+        self.run(main, span);
+        let a = mem::replace(&mut self.state, before);
+        self.run(other, span);
+
+        let b = &mut self.state;
+        if a.lost || b.lost || a.stack.len() != b.stack.len()
+            || a.taken.len() != b.taken.len()
+        {
+            return self.lose();
+        }
+
+        for (x, y) in b.stack.iter_mut().zip(a.stack) {
+            if x.ty != y.ty {
+                *x = Value::any();
+            } else if x.lit != y.lit {
+                *x = Value::of(x.ty);
+            }
+        }
+
+        for (x, y) in b.taken.iter_mut().zip(a.taken) {
+            if *x != y {
+                *x = Type::Any;
+            }
+        }
+    }
+
+    fn define(&mut self, name: Value, body: Value, span: Option<Span>) {
+        let (name, code) = match (name.lit, body.lit) {
+            (Some(PescToken::Str(n)), Some(PescToken::Macro(m))) => (n.to_lowercase(), m),
+            _ => {
+                self.defs_unknown = true;
+                return;
+            },
+        };
+
+        // calls to the function from inside itself can't be known
+        self.defs.insert(name.clone(), None);
+        let sig = self.infer(&code, &body.spans, span, true);
+        self.defs.insert(name, sig);
+    }
+
+    // work out the signature of a macro, if possible.
+    fn infer(&mut self, code: &[PescToken], spans: &[TokenSpan],
+        fallback: Option<Span>, keep_errors: bool) -> Option<Signature>
+    {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+
+        let mut sub = Checker {
+            pesc: self.pesc,
+            state: State::default(),
+            infer: true,
+            defs: mem::take(&mut self.defs),
+            defs_unknown: self.defs_unknown,
+            errors: Vec::new(),
+            depth: self.depth + 1,
+        };
+
+        sub.block(code, spans, fallback);
+
+        self.defs = sub.defs;
+        self.defs_unknown = sub.defs_unknown;
+        if keep_errors {
+            self.errors.extend(sub.errors);
+        }
+
+        if sub.state.lost {
+            return None;
+        }
+
+        Some(Signature {
+            inputs: sub.state.taken,
+            outputs: sub.state.stack.iter().map(|v| v.ty).collect(),
+            more_inputs: false,
+            more_outputs: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib;
+
+    fn check(input: &str) -> Vec<(PescErrorType, usize)> {
+        let mut p = Pesc::new();
+        for func in stdlib::standard().into_iter().chain(stdlib::extended()) {
+            p.load(func.0, func.1, func.2, func.3);
+        }
+
+        let (spans, code) = Pesc::parse(input).unwrap();
+        p.check(&code, &spans).into_iter()
+            .map(|e| (e.kind, e.span.unwrap().col))
+            .collect()
+    }
+
+    fn underflow(f: &str, needed: usize, given: usize) -> PescErrorType {
+        PescErrorType::NotEnoughArguments(Some((f.to_string(), needed, given)))
+    }
+
+    #[test]
+    fn test_signature() {
+        let sig = Signature::parse("... number a -- a list").unwrap();
+        assert_eq!(sig.inputs, vec![Type::Number, Type::Var('a')]);
+        assert_eq!(sig.outputs, vec![Type::Var('a'), Type::List]);
+        assert!(sig.more_inputs && !sig.more_outputs);
+        assert_eq!(sig.to_string(), "... number a -- a list");
+        assert!(Signature::parse("number").is_none());
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(check("1 2 + \\ ×"), vec![]);
+        assert_eq!(check("1 +"), vec![(underflow("add", 2, 1), 3)]);
+        assert_eq!(check("1 \"a\" +"), vec![(PescErrorType::InvalidArgumentType(
+            String::from("number"), String::from("string")), 7)]);
+        assert_eq!(check("[foo] 1 +"), vec![(PescErrorType::UnknownFunction(
+            String::from("foo")), 1)]);
+
+        // macros that are written out are followed into
+        assert_eq!(check("1 { 2 { + } ; } ; +"), vec![(underflow("add", 2, 1), 19)]);
+        assert_eq!(check("{ 1 } { 2 } T ? +"), vec![(underflow("add", 2, 1), 17)]);
+        assert_eq!(check("1 2 0 ø 2 @ + +"), vec![]);
+        assert_eq!(check("'{ \"a\" 1 } \"a\" [at] 1 +"), vec![]);
+        assert_eq!(check("'{ } \"a\" ø"), vec![(PescErrorType::InvalidArgumentType(
+            String::from("number"), String::from("string")), 10)]);
+
+        // and definitions get a signature
+        assert_eq!(check("{ \\ × } \"sq\" [def] [sq]"), vec![(underflow("sq", 1, 0), 20)]);
+        assert_eq!(check("{ \"a\" + } \"f\" [def]"), vec![(PescErrorType::InvalidArgumentType(
+            String::from("number"), String::from("string")), 7)]);
+
+        // but we give up once the stack can't be known
+        assert_eq!(check("{ \\ 0 [eq?] { 1 - [down] } , {} , ? } \"down\" [def] 5 [down] +"), vec![]);
+        assert_eq!(check("1 2 3 3 [list] [explode] +"), vec![]);
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt::{self, Display};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A complex number, with floating-point real and imaginary parts.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// Parse a complex literal such as "3+2i", "-2.5i", or "i", with
+    /// the parts themselves being parsed by `part`.
+    pub fn parse<F>(s: &str, part: F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<f64>
+    {
+        let s = s.strip_suffix('i')?;
+
+        // find the sign separating the real and imaginary parts,
+        // skipping a leading sign and exponent signs ("1e-5")
+        let split = s.char_indices()
+            .rev()
+            .find(|&(i, c)| (c == '+' || c == '-') && i > 0
+                && !s[..i].ends_with(['e', 'E']))
+            .map(|(i, _)| i);
+
+        let (re, im) = match split {
+            Some(i) => (part(&s[..i])?, &s[i..]),
+            None => (0.0, s),
+        };
+
+        let im = match im {
+            "" | "+" => 1.0,
+            "-" => -1.0,
+            _ => part(im.strip_prefix('+').unwrap_or(im))?,
+        };
+
+        Some(Self::new(re, im))
+    }
+
+    pub fn conj(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    /// The modulus (absolute value) of the number.
+    pub fn abs(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The argument (phase angle) of the number, in radians.
+    pub fn arg(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn exp(&self) -> Self {
+        // e^(a + bi) = e^a × (cos b + i sin b)
+        let r = self.re.exp();
+        Self::new(r * self.im.cos(), r * self.im.sin())
+    }
+
+    /// The principal value of the natural logarithm.
+    pub fn ln(&self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    /// The principal square root.
+    pub fn sqrt(&self) -> Self {
+        let r = self.abs();
+        let re = ((r + self.re) / 2.0).sqrt();
+        let im = ((r - self.re) / 2.0).sqrt();
+
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    pub fn pow(&self, exp: &Self) -> Self {
+        if self.re == 0.0 && self.im == 0.0 {
+            return if exp.re == 0.0 && exp.im == 0.0 {
+                Self::new(1.0, 0.0)
+            } else {
+                *self
+            };
+        }
+
+        // z^w = e^(w ln z)
+        (*exp * self.ln()).exp()
+    }
+
+    pub fn sin(&self) -> Self {
+        // sin(a + bi) = sin a cosh b + i cos a sinh b
+        Self::new(self.re.sin() * self.im.cosh(), self.re.cos() * self.im.sinh())
+    }
+
+    pub fn cos(&self) -> Self {
+        // cos(a + bi) = cos a cosh b - i sin a sinh b
+        Self::new(self.re.cos() * self.im.cosh(), -(self.re.sin() * self.im.sinh()))
+    }
+
+    pub fn tan(&self) -> Self {
+        self.sin() / self.cos()
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+}
+
+impl Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        if self.re == 0.0 {
+            write!(f, "{}i", self.im)
+        } else if self.im.is_sign_negative() {
+            write!(f, "{}-{}i", self.re, -self.im)
+        } else {
+            write!(f, "{}+{}i", self.re, self.im)
+        }
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, o: Complex) -> Complex {
+        Complex::new(self.re + o.re, self.im + o.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, o: Complex) -> Complex {
+        Complex::new(self.re - o.re, self.im - o.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, o: Complex) -> Complex {
+        Complex::new(self.re * o.re - self.im * o.im,
+            self.re * o.im + self.im * o.re)
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, o: Complex) -> Complex {
+        //  a + bi     (ac + bd) + (bc - ad)i
+        //  ──────  =  ──────────────────────
+        //  c + di           c² + d²
+        let d = o.re * o.re + o.im * o.im;
+        Complex::new((self.re * o.re + self.im * o.im) / d,
+            (self.im * o.re - self.re * o.im) / d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Complex, b: Complex) -> bool {
+        (a.re - b.re).abs() < 1e-12 && (a.im - b.im).abs() < 1e-12
+    }
+
+    #[test]
+    fn test_parse() {
+        let p = |s: &str| Complex::parse(s, |x| x.parse().ok());
+        assert_eq!(p("3+2i"), Some(Complex::new(3.0, 2.0)));
+        assert_eq!(p("3-2i"), Some(Complex::new(3.0, -2.0)));
+        assert_eq!(p("-2.5i"), Some(Complex::new(0.0, -2.5)));
+        assert_eq!(p("i"), Some(Complex::new(0.0, 1.0)));
+        assert_eq!(p("1e-5+1e+2i"), Some(Complex::new(1e-5, 1e2)));
+        assert_eq!(p("3+2"), None);
+        assert_eq!(p("x+2i"), None);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Complex::new(3.0, 2.0).to_string(), "3+2i");
+        assert_eq!(Complex::new(3.0, -2.0).to_string(), "3-2i");
+        assert_eq!(Complex::new(0.0, 1.0).to_string(), "1i");
+    }
+
+    #[test]
+    fn test_math() {
+        let i = Complex::new(0.0, 1.0);
+        assert_eq!(i * i, Complex::from(-1.0));
+        assert_eq!(Complex::from(-4.0).sqrt(), Complex::new(0.0, 2.0));
+        assert!(close(i.pow(&Complex::from(2.0)), Complex::from(-1.0)));
+        assert!(close(Complex::from(-1.0).ln(), Complex::new(0.0, std::f64::consts::PI)));
+        assert!(close((i * Complex::from(std::f64::consts::PI)).exp(), Complex::from(-1.0)));
+        assert!(close(i.sin(), Complex::new(0.0, 1_f64.sinh())));
+        assert_eq!(Complex::new(3.0, 4.0).abs(), 5.0);
+        assert_eq!(Complex::new(1.0, 1.0) / Complex::new(1.0, 1.0), Complex::from(1.0));
+    }
+}
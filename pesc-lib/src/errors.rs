@@ -1,8 +1,9 @@
 use std::fmt::{self, Display};
 use std::error::*;
 use crate::pesc::*;
+use crate::lexer::Span;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum PescErrorType {
     // <func>
     UnknownFunction(String),
@@ -10,7 +11,8 @@ pub enum PescErrorType {
     // <token> (e.g. "[", "(")
     UnmatchedToken(char),
 
-    NotEnoughArguments,
+    // <function>, <arguments needed>, <arguments given>, if known
+    NotEnoughArguments(Option<(String, usize, usize)>),
 
     // <expected>, <found>
     InvalidArgumentType(String, String),
@@ -20,85 +22,157 @@ pub enum PescErrorType {
 
     EmptyLiteral,
 
+    // <escape> (e.g. "\\q")
+    InvalidEscape(String),
+
     // <a>, <b>
-    DivideByZero(f64, f64),
+    DivideByZero(PescNumber, PescNumber),
 
     // <index>, <length>
-    OutOfBounds(f64, usize),
+    OutOfBounds(PescNumber, usize),
+
+    // <kind of sequence>, <index>, <length>
+    IndexOutOfBounds(String, PescNumber, usize),
+
+    // <kind of sequence>
+    EmptySequence(String),
+
+    // <key>
+    NoSuchKey(String),
+
+    // <key>
+    MissingValue(String),
 
     // <found>
     InvalidBoolean(PescToken),
 
+    // <register>
+    EmptyRegister(String),
+
+    // <limit name>, <limit>
+    LimitExceeded(String, usize),
+
+    // <expected>
+    InvalidSession(String),
+
     Other(String),
 }
 
-impl ToString for PescErrorType {
-    fn to_string(&self) -> String {
-        match self {
+impl Display for PescErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let msg = match self {
             PescErrorType::UnknownFunction(f) =>
                 format!("I have no idea what {} means.", f),
             PescErrorType::UnmatchedToken(t) =>
                 format!("Where's the matching '{}'?", t),
-            PescErrorType::NotEnoughArguments =>
-                format!("I need just 1 more argument, OK?"),
+            PescErrorType::NotEnoughArguments(Some((_, needed, given)))
+                if needed - given > 1 =>
+                format!("I need {} more arguments, OK?", needed - given),
+            PescErrorType::NotEnoughArguments(_) =>
+                "I need just 1 more argument, OK?".to_string(),
             PescErrorType::InvalidArgumentType(h, a) =>
                 format!("I wanted a {}, but you gave a {}", h, a),
             PescErrorType::InvalidNumberLit(f) =>
                 format!("What makes you think '{}' is a number?", f),
             PescErrorType::EmptyLiteral =>
-                format!("I don't know what to do with an empty literal."),
+                "I don't know what to do with an empty literal.".to_string(),
+            PescErrorType::InvalidEscape(e) =>
+                format!("What's '{}' supposed to mean in a string?", e),
             PescErrorType::DivideByZero(a, b) =>
                 format!("You can't divide {} by {}, so don't try.", a, b),
             PescErrorType::OutOfBounds(i, _) =>
-                format!("The stack isn't as big as you think ({} is out of bounds)", *i as usize),
+                format!("The stack isn't as big as you think ({} is out of bounds)", i),
+            PescErrorType::IndexOutOfBounds(k, i, l) =>
+                format!("There's nothing at {} in a {} of length {}.", i, k, l),
+            PescErrorType::EmptySequence(k) =>
+                format!("There's nothing in that {}; it's empty.", k),
+            PescErrorType::NoSuchKey(k) =>
+                format!("There's no {:?} in that map.", k),
+            PescErrorType::MissingValue(k) =>
+                format!("What's the value of {:?} supposed to be?", k),
             PescErrorType::InvalidBoolean(found) =>
                 format!("Uh, is {} supposed to be true or false?", found),
+            PescErrorType::EmptyRegister(r) =>
+                format!("There's nothing in register {:?}.", r),
+            PescErrorType::LimitExceeded(l, n) =>
+                format!("That went past the {} limit of {}.", l, n),
+            PescErrorType::InvalidSession(e) =>
+                format!("I expected {} here, but this session says otherwise.", e),
             PescErrorType::Other(msg) => msg.clone(),
-        }
+        };
+
+        write!(f, "{}", msg)
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct PescError {
-    pub ch: Option<usize>,
+    pub span: Option<Span>,
     pub token: Option<PescToken>,
     pub kind: PescErrorType,
 }
 
 impl PescError {
-    pub fn new(c: Option<usize>, t: Option<PescToken>, k: PescErrorType)
+    pub fn new(s: Option<Span>, t: Option<PescToken>, k: PescErrorType)
         -> Self
     {
         Self {
-            ch: c,
+            span: s,
             token: t,
             kind: k
         }
     }
 
     pub fn hints(&self) -> Vec<String> {
-        match self.kind {
+        match &self.kind {
             PescErrorType::UnknownFunction(_) => vec![
                 "is the function loaded correctly?".to_string(),
             ],
             PescErrorType::UnmatchedToken(_) => vec![],
 
-            // TODO: check function documentation and hint
-            // with the correct number of arguments
-            PescErrorType::NotEnoughArguments => vec![],
+            PescErrorType::NotEnoughArguments(Some((f, needed, given))) => vec![
+                format!("{} needs {} argument{}, got {}.", f, needed,
+                    if *needed == 1 { "" } else { "s" }, given),
+            ],
+            PescErrorType::NotEnoughArguments(None) => vec![],
             PescErrorType::InvalidArgumentType(_, _) => vec![],
             PescErrorType::InvalidNumberLit(_) => vec![
-                "number literals may only contain character [0-9_\\.]".to_string(),
-                "bases other than decimal are currently not supported.".to_string(),
+                "numbers look like 12, _3.5, 1_000, 6.02e23, 1e_9, 2i, or (1/3).".to_string(),
+                "hex, octal, and binary numbers start with 0x, 0o, and 0b.".to_string(),
             ],
             PescErrorType::EmptyLiteral => vec![],
+            PescErrorType::InvalidEscape(_) => vec![
+                "strings can contain \\n, \\t, \\r, \\\", \\\\, and \\u{...} escapes.".to_string(),
+                "backslashes are left alone in `raw strings`.".to_string(),
+            ],
             PescErrorType::DivideByZero(_, _) => vec![],
             PescErrorType::OutOfBounds(_, a) => vec![
                 format!("the stack is {} elements long.", a),
             ],
+            PescErrorType::IndexOutOfBounds(k, _, _) => vec![
+                format!("{}s are indexed from 0.", k),
+            ],
+            PescErrorType::EmptySequence(_) => vec![
+                "use [len] to check whether it's empty first.".to_string(),
+            ],
+            PescErrorType::NoSuchKey(_) => vec![
+                "use [has] to check whether a map has a key.".to_string(),
+            ],
+            PescErrorType::MissingValue(_) => vec![
+                "maps are written as keys followed by their values: '{ \"key\" value }".to_string(),
+            ],
             PescErrorType::InvalidBoolean(_) => vec![
                 "only tokens of type `number`, `string`, and `bool` can be cast as boolean.".to_string()
             ],
+            PescErrorType::EmptyRegister(_) => vec![
+                "use [store] or [rpush] to put a value in a register.".to_string(),
+            ],
+            PescErrorType::LimitExceeded(_, _) => vec![
+                "the program may be stuck in a loop, or working on too large an input.".to_string(),
+            ],
+            PescErrorType::InvalidSession(_) => vec![
+                "sessions should be written by `Pesc::save`.".to_string(),
+            ],
             PescErrorType::Other(_) => vec![],
         }
     }
@@ -109,6 +183,6 @@ impl Error for PescError {
 
 impl Display for PescError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "{}", self.kind.to_string())
+        write!(f, "{}", self.kind)
     }
 }
@@ -0,0 +1,127 @@
+use std::fmt::{self, Display};
+use crate::check::{Signature, Type};
+
+/// The part of the standard library a function belongs to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Arithmetic,
+    Stack,
+    Bitwise,
+    Register,
+    History,
+    Boolean,
+    List,
+    Map,
+    String,
+    Combinator,
+    Trigonometry,
+    Math,
+    Misc,
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Category::Arithmetic   => write!(f, "arithmetic"),
+            Category::Stack        => write!(f, "stack"),
+            Category::Bitwise      => write!(f, "bitwise"),
+            Category::Register     => write!(f, "register"),
+            Category::History      => write!(f, "history"),
+            Category::Boolean      => write!(f, "boolean"),
+            Category::List         => write!(f, "list"),
+            Category::Map          => write!(f, "map"),
+            Category::String       => write!(f, "string"),
+            Category::Combinator   => write!(f, "combinator"),
+            Category::Trigonometry => write!(f, "trigonometry"),
+            Category::Math         => write!(f, "math"),
+            Category::Misc         => write!(f, "misc"),
+        }
+    }
+}
+
+/// What a function loaded with Pesc::load takes, what it gives back,
+/// and what it does.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuncInfo {
+    pub category: Category,
+    pub sig: Signature,
+
+    /// The names of the inputs, in the same order as `sig.inputs`.
+    pub args: Vec<String>,
+
+    /// A one-line description.
+    pub doc: String,
+}
+
+impl FuncInfo {
+    /// Describe a function. `sig` is its stack effect (see
+    /// check::Signature), where each input can be named: "a:number
+    /// b:number -- number". Inputs without a name are named after
+    /// their type.
+    ///
+    /// Panics if the signature can't be parsed.
+    pub fn new(category: Category, sig: &str, doc: &str) -> Self {
+        let parsed = Signature::parse(sig)
+            .unwrap_or_else(|| panic!("invalid signature {:?}", sig));
+
+        let args = sig.split("--").next().unwrap_or("")
+            .split_whitespace()
+            .filter(|w| *w != "...")
+            .map(|w| w.split(':').next().unwrap_or(w).to_string())
+            .collect();
+
+        Self { category, sig: parsed, args, doc: doc.to_string() }
+    }
+
+    /// How many arguments the function takes, not counting any that
+    /// can't be known ahead of time.
+    pub fn arity(&self) -> usize {
+        self.sig.inputs.len()
+    }
+
+    pub fn returns(&self) -> &[Type] {
+        &self.sig.outputs
+    }
+}
+
+impl Display for FuncInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let mut words = Vec::new();
+
+        if self.sig.more_inputs {
+            words.push(String::from("..."));
+        }
+        for (name, ty) in self.args.iter().zip(&self.sig.inputs) {
+            match ty.to_string() {
+                t if t == *name => words.push(t),
+                t => words.push(format!("{}:{}", name, t)),
+            }
+        }
+
+        words.push(String::from("--"));
+        words.extend(self.sig.outputs.iter().map(|t| t.to_string()));
+        if self.sig.more_outputs {
+            words.push(String::from("..."));
+        }
+
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_info() {
+        let info = FuncInfo::new(Category::List,
+            "... l:list n:number any -- list", "something");
+        assert_eq!(info.args, vec!["l", "n", "any"]);
+        assert_eq!(info.arity(), 3);
+        assert_eq!(info.returns(), &[Type::List]);
+        assert_eq!(info.to_string(), "... l:list n:number any -- list");
+
+        let info = FuncInfo::new(Category::Misc, "-- number", "");
+        assert_eq!(info.to_string(), "-- number");
+    }
+}
@@ -0,0 +1,387 @@
+use std::iter::Peekable;
+use std::str::CharIndices;
+use crate::errors::*;
+use crate::pesc::{BOOLEAN_TRUE, BOOLEAN_FALSE};
+
+/// A region of source code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Span {
+    /// Byte offset of the first character.
+    pub start: usize,
+
+    /// Byte offset just past the last character.
+    pub end: usize,
+
+    /// Line of the first character, starting at 1.
+    pub line: usize,
+
+    /// Column (in characters, not bytes) of the first character,
+    /// starting at 1.
+    pub col: usize,
+}
+
+/// The span of a parsed token. Macros also hold the spans of the
+/// tokens inside them, so that this mirrors the shape of the parsed
+/// `Vec<PescToken>`.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct TokenSpan {
+    pub span: Span,
+    pub inner: Vec<TokenSpan>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexemeKind {
+    /// A bare number literal, such as "12", "_3.5", "0xff", "1e_9", or
+    /// "2i".
+    Number(String),
+
+    /// A parenthesized number literal, such as "(3+2i)". Only the
+    /// contents are kept.
+    Literal(String),
+
+    Str(String),
+    Func(String),
+    MacroStart,
+    MacroEnd,
+
+    /// The "'(" that opens a list.
+    ListStart,
+    ListEnd,
+
+    /// The "'{" that opens a map, which is closed by a MacroEnd.
+    MapStart,
+
+    Bool(bool),
+    Symbol(char),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lexeme {
+    pub kind: LexemeKind,
+    pub span: Span,
+}
+
+/// Splits Pesc source into lexemes, keeping track of where in the
+/// source each one came from.
+pub struct Lexer<'a> {
+    src: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    col: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            chars: src.char_indices().peekable(),
+            line: 1,
+            col: 1,
+        }
+    }
+
+    fn next_char(&mut self) -> Option<(usize, char)> {
+        let (i, c) = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+
+        Some((i, c))
+    }
+
+    fn offset(&mut self) -> usize {
+        match self.chars.peek() {
+            Some(&(i, _)) => i,
+            None => self.src.len(),
+        }
+    }
+
+    // consume chars while until() returns false, returning the chomped
+    // text and whether we reached the end of the input first.
+    fn chomp<F>(&mut self, until: F) -> (&'a str, bool)
+    where
+        F: Fn(char) -> bool
+    {
+        let start = self.offset();
+
+        while let Some(&(_, c)) = self.chars.peek() {
+            if until(c) {
+                return (&self.src[start..self.offset()], false);
+            }
+
+            self.next_char();
+        }
+
+        (&self.src[start..], true)
+    }
+
+    // consume a delimited chunk of text, such as a string or a function
+    // name, along with its closing delimiter.
+    fn delimited(&mut self, open: char, close: char, span: &mut Span)
+        -> Result<&'a str, PescError>
+    {
+        let (s, eof) = self.chomp(|c| c == close);
+
+        if eof {
+            span.end = self.src.len();
+            return Err(PescError::new(Some(*span), None,
+                PescErrorType::UnmatchedToken(open)));
+        }
+
+        self.next_char();
+        Ok(s)
+    }
+
+    // consume a string along with its closing '"', decoding any escapes
+    // on the way.
+    fn string(&mut self, span: &mut Span) -> Result<String, PescError> {
+        let mut s = String::new();
+
+        loop {
+            let esc = Span {
+                start: self.offset(), end: 0,
+                line: self.line, col: self.col,
+            };
+
+            match self.next_char() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) if self.chars.peek().is_some() =>
+                    s.push(self.escape(esc)?),
+                Some((_, c)) => s.push(c),
+                None => {
+                    span.end = self.src.len();
+                    return Err(PescError::new(Some(*span), None,
+                        PescErrorType::UnmatchedToken('"')));
+                },
+            }
+        }
+    }
+
+    // decode the escape after a backslash: "\n", "\t", "\r", "\"",
+    // "\\", or a unicode codepoint in hex, such as "\u{1F600}".
+    fn escape(&mut self, mut span: Span) -> Result<char, PescError> {
+        let c = match self.next_char().map(|(_, c)| c) {
+            Some('n') => Some('\n'),
+            Some('t') => Some('\t'),
+            Some('r') => Some('\r'),
+            Some('"') => Some('"'),
+            Some('\\') => Some('\\'),
+            Some('u') if self.chars.peek().map(|&(_, c)| c) == Some('{') => {
+                self.next_char();
+                let (hex, _) = self.chomp(|c| c == '}' || c == '"');
+
+                match self.chars.peek().map(|&(_, c)| c) {
+                    Some('}') => {
+                        self.next_char();
+                        u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                    },
+                    _ => None,
+                }
+            },
+            _ => None,
+        };
+
+        span.end = self.offset();
+        c.ok_or_else(|| PescError::new(Some(span), None,
+            PescErrorType::InvalidEscape(self.src[span.start..span.end].to_string())))
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Lexeme, PescError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut span = Span {
+                start: self.offset(), end: 0,
+                line: self.line, col: self.col,
+            };
+
+            let kind = match self.next_char()?.1 {
+                // whitespace
+                '\n' | '\t' | ' ' => continue,
+
+                // comments
+                '#' => {
+                    self.chomp(|c| c == '\n' || c == '\\');
+                    self.next_char();
+                    continue;
+                },
+
+                // number literals
+                c if c.is_ascii_digit() || c == '.' || c == '_' => {
+                    let end = span.start + number_len(&self.src[span.start..]);
+                    while self.offset() < end {
+                        self.next_char();
+                    }
+
+                    LexemeKind::Number(self.src[span.start..end].to_string())
+                },
+
+                '(' => match self.delimited('(', ')', &mut span) {
+                    Ok(s) => LexemeKind::Literal(s.to_string()),
+                    Err(e) => return Some(Err(e)),
+                },
+                '"' => match self.string(&mut span) {
+                    Ok(s) => LexemeKind::Str(s),
+                    Err(e) => return Some(Err(e)),
+                },
+
+                // raw strings, in which backslashes are left alone
+                '`' => match self.delimited('`', '`', &mut span) {
+                    Ok(s) => LexemeKind::Str(s.to_string()),
+                    Err(e) => return Some(Err(e)),
+                },
+                '[' => match self.delimited('[', ']', &mut span) {
+                    Ok(s) => LexemeKind::Func(s.to_string()),
+                    Err(e) => return Some(Err(e)),
+                },
+
+                '{' => LexemeKind::MacroStart,
+                '}' => LexemeKind::MacroEnd,
+
+                '\'' if self.chars.peek().map(|&(_, c)| c) == Some('(') => {
+                    self.next_char();
+                    LexemeKind::ListStart
+                },
+                '\'' if self.chars.peek().map(|&(_, c)| c) == Some('{') => {
+                    self.next_char();
+                    LexemeKind::MapStart
+                },
+                ')' => LexemeKind::ListEnd,
+
+                BOOLEAN_TRUE => LexemeKind::Bool(true),
+                BOOLEAN_FALSE => LexemeKind::Bool(false),
+
+                // treat unknown characters as symbols aka operators
+                c => LexemeKind::Symbol(c),
+            };
+
+            span.end = self.offset();
+            return Some(Ok(Lexeme { kind, span }));
+        }
+    }
+}
+
+// the length in bytes of the bare number literal at the start of s:
+// any leading '_' signs, then either a radix prefix and the digits of
+// that radix ("0xff"), or decimal digits with an optional exponent
+// ("6.02e23", "1e_9") and imaginary suffix ("2i"). underscores can go
+// anywhere among the digits. an 'e' that isn't followed by a digit
+// isn't part of the number, so "2e" is still 2 times Euler's number.
+fn number_len(s: &str) -> usize {
+    let b = s.as_bytes();
+    let run = |mut i: usize, digit: &dyn Fn(u8) -> bool| {
+        while i < b.len() && (digit(b[i]) || b[i] == b'_') {
+            i += 1;
+        }
+        i
+    };
+
+    let start = b.iter().take_while(|&&c| c == b'_').count();
+    let radix: Option<fn(&u8) -> bool> = match b.get(start..start + 2) {
+        Some(b"0x") => Some(u8::is_ascii_hexdigit),
+        Some(b"0o") => Some(|c| (b'0'..=b'7').contains(c)),
+        Some(b"0b") => Some(|c| *c == b'0' || *c == b'1'),
+        _ => None,
+    };
+
+    if let Some(digit) = radix {
+        let end = run(start + 2, &|c| digit(&c));
+        if b[start + 2..end].iter().any(digit) {
+            return end;
+        }
+    }
+
+    let mut i = run(start, &|c| c.is_ascii_digit() || c == b'.');
+
+    if b.get(i) == Some(&b'e') {
+        let exp = if b.get(i + 1) == Some(&b'_') { i + 2 } else { i + 1 };
+        if b.get(exp).is_some_and(u8::is_ascii_digit) {
+            i = run(exp, &|c| c.is_ascii_digit());
+        }
+    }
+
+    if b.get(i) == Some(&b'i') {
+        i += 1;
+    }
+
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(s: &str) -> Vec<(LexemeKind, usize, usize, usize, usize)> {
+        Lexer::new(s)
+            .map(|l| l.unwrap())
+            .map(|l| (l.kind, l.span.start, l.span.end, l.span.line, l.span.col))
+            .collect()
+    }
+
+    #[test]
+    fn test_spans() {
+        assert_eq!(lex("12 [add]\n \"×\" ×{"), vec![
+            (LexemeKind::Number("12".into()), 0, 2, 1, 1),
+            (LexemeKind::Func("add".into()), 3, 8, 1, 4),
+            (LexemeKind::Str("×".into()), 10, 14, 2, 2),
+            (LexemeKind::Symbol('×'), 15, 17, 2, 6),
+            (LexemeKind::MacroStart, 17, 18, 2, 7),
+        ]);
+
+        assert_eq!(lex("'(') '"), vec![
+            (LexemeKind::ListStart, 0, 2, 1, 1),
+            (LexemeKind::Symbol('\''), 2, 3, 1, 3),
+            (LexemeKind::ListEnd, 3, 4, 1, 4),
+            (LexemeKind::Symbol('\''), 5, 6, 1, 6),
+        ]);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let numbers = |s| lex(s).into_iter().map(|l| l.0).collect::<Vec<_>>();
+        let n = |s: &str| LexemeKind::Number(s.into());
+
+        assert_eq!(numbers("_0x_ff 0o17 0b1_0 1_000.5 6.02e23 1e_9 2i"), vec![
+            n("_0x_ff"), n("0o17"), n("0b1_0"), n("1_000.5"),
+            n("6.02e23"), n("1e_9"), n("2i"),
+        ]);
+        assert_eq!(numbers("2e 0b2 3e_"), vec![
+            n("2"), LexemeKind::Symbol('e'), n("0"), LexemeKind::Symbol('b'),
+            n("2"), n("3"), LexemeKind::Symbol('e'), n("_"),
+        ]);
+    }
+
+    #[test]
+    fn test_strings() {
+        let strings = |s| lex(s).into_iter().map(|l| l.0).collect::<Vec<_>>();
+        let st = |s: &str| LexemeKind::Str(s.into());
+
+        assert_eq!(strings(r#""a\"b\\c\n\t\r" "\u{1F600}\u{e9}" `\n"\`"#), vec![
+            st("a\"b\\c\n\t\r"), st("😀é"), st("\\n\"\\"),
+        ]);
+
+        for (src, esc, start) in &[(r#""ab\q""#, "\\q", 3), (r#""\u{110000}""#, "\\u{110000}", 1),
+            (r#""\u{41""#, "\\u{41", 1)]
+        {
+            let e = Lexer::new(src).next().unwrap().unwrap_err();
+            assert_eq!(e.kind, PescErrorType::InvalidEscape(esc.to_string()));
+            assert_eq!(e.span.map(|s| (s.start, s.col)), Some((*start, start + 1)));
+        }
+
+        let e = Lexer::new("\"a\\").next().unwrap().unwrap_err();
+        assert_eq!(e.kind, PescErrorType::UnmatchedToken('"'));
+    }
+
+    #[test]
+    fn test_unmatched() {
+        let e = Lexer::new("1 [foo").nth(1).unwrap().unwrap_err();
+        assert_eq!(e.kind, PescErrorType::UnmatchedToken('['));
+        assert_eq!(e.span, Some(Span { start: 2, end: 6, line: 1, col: 3 }));
+    }
+}
@@ -1,9 +1,43 @@
+// errors carry the offending token and a copy of the stack, which
+// makes them rather large; that's fine, they're not on a hot path.
+#![allow(clippy::result_large_err)]
+
+/// Arbitrary-precision integers, used by Pesc's numbers.
+pub mod bigint;
+
+/// Exact rational numbers, used by Pesc's numbers.
+pub mod rational;
+
+/// Complex numbers, used by Pesc's numbers.
+pub mod complex;
+
+/// Pesc's numeric type, and arithmetic on it.
+pub mod number;
+
+/// Splitting Pesc source into tokens with source spans.
+pub mod lexer;
+
 /// Various Pesc parsing/runtime errors.
 pub mod errors;
 
+/// Compiled Pesc code.
+pub mod bytecode;
+
 /// Core Pesc functionality.
 pub mod pesc;
 
+/// Writing Pesc tokens back out as Pesc source.
+pub mod pretty;
+
+/// Checking Pesc code for mistakes without running it.
+pub mod check;
+
+/// Descriptions of the functions that can be loaded into Pesc.
+pub mod info;
+
+/// Saving and restoring the state of the interpreter.
+pub mod session;
+
 /// Pesc's standard library (arithmetic functions, log,
 /// factorial, pow, sqrt, etc)
 pub mod stdlib;
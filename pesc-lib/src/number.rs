@@ -0,0 +1,546 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use crate::bigint::BigInt;
+use crate::complex::Complex;
+use crate::rational::Rational;
+
+/// The largest result, in bits, that exact operations whose cost grows
+/// with the size of their result (like [pow] and [shl]) will compute
+/// exactly; anything bigger is approximated with a float instead.
+pub const MAX_EXACT_BITS: usize = 1 << 16;
+
+/// A Pesc number. Integral values are kept as arbitrary-precision
+/// integers and non-integral ones as exact rationals; we only fall
+/// back to floating point when an operation can't give an exact
+/// result (e.g. [sqrt], or [sin]), and promote to complex numbers
+/// when there isn't a real result at all (e.g. -1 [sqrt]).
+#[derive(Clone, Debug)]
+pub enum PescNumber {
+    Int(BigInt),
+
+    // never has a denominator of 1; see PescNumber::from(Rational)
+    Ratio(Rational),
+
+    Float(f64),
+
+    // never has an imaginary part of 0; see PescNumber::from(Complex)
+    Complex(Complex),
+}
+
+impl PescNumber {
+    /// Parse a number literal (with the underscores already removed).
+    /// Plain decimal literals are exact (so "0.1" really is 1/10), as
+    /// are fractions ("1/3") and integers in hex, octal, or binary
+    /// ("0xff", "0o17", "0b101"), literals ending in 'i' are complex,
+    /// and everything else, such as "6.02e23", is handed off to the
+    /// float parser.
+    pub fn parse(s: &str) -> Option<Self> {
+        let unsigned = s.trim_start_matches(['-', '+']);
+        let radix = match unsigned.get(..2) {
+            Some("0x") => Some(16),
+            Some("0o") => Some(8),
+            Some("0b") => Some(2),
+            _ => None,
+        };
+
+        if let Some(radix) = radix {
+            let sign = &s[..s.len() - unsigned.len()];
+            let digits = format!("{}{}", sign, &unsigned[2..]);
+            return BigInt::parse_radix(&digits, radix).map(PescNumber::Int);
+        }
+
+        if s.ends_with('i') {
+            let c = Complex::parse(s, |p| Self::parse(p).map(|n| n.to_f64()))?;
+            return Some(PescNumber::from(c));
+        }
+
+        if let Some((num, den)) = s.split_once('/') {
+            let r = Rational::new(BigInt::parse(num)?, BigInt::parse(den)?)?;
+            return Some(PescNumber::from(r));
+        }
+
+        if let Some(i) = BigInt::parse(s) {
+            return Some(PescNumber::Int(i));
+        }
+
+        if let Some(r) = Rational::parse_decimal(s) {
+            return Some(PescNumber::from(r));
+        }
+
+        s.parse::<f64>().ok().map(PescNumber::Float)
+    }
+
+    /// Parse a number as it's written in Pesc source, where '_' is a
+    /// negative sign at the start of the literal or of an exponent
+    /// ("_5", "1e_9") and a digit separator anywhere else ("1_000",
+    /// "0x_ff"). A '-' prefix works too.
+    pub fn parse_literal(lit: &str) -> Option<Self> {
+        let hex = lit.trim_start_matches('_').starts_with("0x");
+        let mut s = String::with_capacity(lit.len());
+
+        for (i, c) in lit.char_indices() {
+            match c {
+                '_' if i == 0 => s.push('-'),
+                '_' if !hex && s.ends_with('e') => s.push('-'),
+                '_' => (),
+                c => s.push(c),
+            }
+        }
+
+        Self::parse(&s)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            PescNumber::Int(i) => i.is_zero(),
+            PescNumber::Ratio(r) => r.is_zero(),
+            PescNumber::Float(f) => *f == 0.0,
+            PescNumber::Complex(_) => false,
+        }
+    }
+
+    pub fn is_complex(&self) -> bool {
+        matches!(self, PescNumber::Complex(_))
+    }
+
+    /// Convert to a float; for complex numbers, this is just the
+    /// real part.
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            PescNumber::Int(i) => i.to_f64(),
+            PescNumber::Ratio(r) => r.to_f64(),
+            PescNumber::Float(f) => *f,
+            PescNumber::Complex(c) => c.re,
+        }
+    }
+
+    pub fn to_complex(&self) -> Complex {
+        match self {
+            PescNumber::Complex(c) => *c,
+            _ => Complex::from(self.to_f64()),
+        }
+    }
+
+    /// Get the exact value of the number, if it has one.
+    pub fn to_rational(&self) -> Option<Rational> {
+        match self {
+            PescNumber::Int(i) => Some(Rational::from(i.clone())),
+            PescNumber::Ratio(r) => Some(r.clone()),
+            PescNumber::Float(_)
+            | PescNumber::Complex(_) => None,
+        }
+    }
+
+    /// Get the integer value, if there is one; floats without a
+    /// fractional part count as well.
+    pub fn to_int(&self) -> Option<BigInt> {
+        match self {
+            PescNumber::Int(i) => Some(i.clone()),
+            PescNumber::Ratio(_)
+            | PescNumber::Complex(_) => None,
+            PescNumber::Float(f) => BigInt::from_f64(*f),
+        }
+    }
+
+    pub fn to_usize(&self) -> Option<usize> {
+        self.to_int().and_then(|i| i.to_usize())
+    }
+
+    /// The absolute value; for complex numbers, the modulus.
+    pub fn abs(&self) -> Self {
+        match self {
+            PescNumber::Int(i) => PescNumber::Int(i.abs()),
+            PescNumber::Ratio(r) => PescNumber::Ratio(r.abs()),
+            PescNumber::Float(f) => PescNumber::Float(f.abs()),
+            PescNumber::Complex(c) => PescNumber::Float(c.abs()),
+        }
+    }
+
+    /// Raise self to the power of `exp`. Exact numbers raised to
+    /// integer powers stay exact as long as the result fits in about
+    /// MAX_EXACT_BITS bits, and negative numbers raised to fractional
+    /// powers become complex.
+    pub fn pow(&self, exp: &Self) -> Self {
+        if self.is_complex() || exp.is_complex()
+            || (self.to_f64() < 0.0 && exp.to_f64().fract() != 0.0)
+        {
+            // avoid rounding noise in the real part of e.g. -4^0.5
+            if exp.to_f64() == 0.5 {
+                return self.sqrt();
+            }
+
+            return PescNumber::from(self.to_complex().pow(&exp.to_complex()));
+        }
+
+        // roughly how many bits the exact result would take; 0, 1,
+        // and -1 stay small no matter the exponent
+        let bits = match self.to_rational() {
+            Some(r) => r.numer().bits().max(r.denom().bits()) - 1,
+            None => 0,
+        };
+
+        let e = match exp {
+            PescNumber::Int(e) => e.to_i64().filter(|e| {
+                (e.unsigned_abs() as usize).saturating_mul(bits) <= MAX_EXACT_BITS
+            }),
+            _ => None,
+        };
+
+        if let (PescNumber::Int(b), Some(e)) = (self, e) {
+            if e >= 0 {
+                return PescNumber::Int(b.pow(e as u32));
+            }
+        }
+
+        if let (Some(b), Some(e)) = (self.to_rational(), e) {
+            if let Some(r) = b.pow(e) {
+                return PescNumber::from(r);
+            }
+        }
+
+        PescNumber::Float(self.to_f64().powf(exp.to_f64()))
+    }
+
+    /// The principal square root.
+    pub fn sqrt(&self) -> Self {
+        if self.is_complex() || self.to_f64() < 0.0 {
+            PescNumber::from(self.to_complex().sqrt())
+        } else {
+            PescNumber::Float(self.to_f64().sqrt())
+        }
+    }
+
+    /// The logarithm of self with respect to `base`.
+    pub fn log(&self, base: &Self) -> Self {
+        if self.is_complex() || base.is_complex()
+            || self.to_f64() < 0.0 || base.to_f64() < 0.0
+        {
+            PescNumber::from(self.to_complex().ln() / base.to_complex().ln())
+        } else {
+            PescNumber::Float(self.to_f64().log(base.to_f64()))
+        }
+    }
+
+    pub fn sin(&self) -> Self {
+        self.trig(Complex::sin, f64::sin)
+    }
+
+    pub fn cos(&self) -> Self {
+        self.trig(Complex::cos, f64::cos)
+    }
+
+    pub fn tan(&self) -> Self {
+        self.trig(Complex::tan, f64::tan)
+    }
+
+    fn trig(&self, cplx: fn(&Complex) -> Complex, real: fn(f64) -> f64) -> Self {
+        match self {
+            PescNumber::Complex(c) => PescNumber::from(cplx(c)),
+            _ => PescNumber::Float(real(self.to_f64())),
+        }
+    }
+
+    /// Round down to the nearest integer.
+    pub fn floor(&self) -> Self {
+        self.rounded(Rational::floor, f64::floor)
+    }
+
+    /// Round up to the nearest integer.
+    pub fn ceil(&self) -> Self {
+        self.rounded(Rational::ceil, f64::ceil)
+    }
+
+    /// Round to the nearest integer, rounding half-way cases away
+    /// from zero.
+    pub fn round(&self) -> Self {
+        self.rounded(Rational::round, f64::round)
+    }
+
+    fn rounded(&self, exact: fn(&Rational) -> BigInt, float: fn(f64) -> f64)
+        -> Self
+    {
+        match self {
+            PescNumber::Int(_)
+            | PescNumber::Complex(_) => self.clone(),
+            PescNumber::Ratio(r) => PescNumber::Int(exact(r)),
+            PescNumber::Float(v) => match BigInt::from_f64(float(*v)) {
+                Some(i) => PescNumber::Int(i),
+                None => PescNumber::Float(float(*v)),
+            },
+        }
+    }
+
+    // apply an operation to two numbers, using the complex version
+    // if either is complex, the integer version if both are integers
+    // and it gives a result, then the exact version if neither is a
+    // float, and only then the float version.
+    fn binop<C, I, R, F>(&self, other: &Self, cplx: C, int: I, ratio: R, float: F)
+        -> Self
+    where
+        C: Fn(Complex, Complex) -> Complex,
+        I: Fn(&BigInt, &BigInt) -> Option<BigInt>,
+        R: Fn(&Rational, &Rational) -> Option<Rational>,
+        F: Fn(f64, f64) -> f64,
+    {
+        if self.is_complex() || other.is_complex() {
+            return PescNumber::from(cplx(self.to_complex(), other.to_complex()));
+        }
+
+        if let (PescNumber::Int(a), PescNumber::Int(b)) = (self, other) {
+            if let Some(r) = int(a, b) {
+                return PescNumber::Int(r);
+            }
+        }
+
+        if let (Some(a), Some(b)) = (self.to_rational(), other.to_rational()) {
+            if let Some(r) = ratio(&a, &b) {
+                return PescNumber::from(r);
+            }
+        }
+
+        PescNumber::Float(float(self.to_f64(), other.to_f64()))
+    }
+}
+
+impl From<Rational> for PescNumber {
+    fn from(r: Rational) -> Self {
+        if r.is_integer() {
+            PescNumber::Int(r.numer().clone())
+        } else {
+            PescNumber::Ratio(r)
+        }
+    }
+}
+
+impl From<Complex> for PescNumber {
+    fn from(c: Complex) -> Self {
+        if c.im == 0.0 {
+            PescNumber::Float(c.re)
+        } else {
+            PescNumber::Complex(c)
+        }
+    }
+}
+
+impl From<BigInt> for PescNumber {
+    fn from(i: BigInt) -> Self {
+        PescNumber::Int(i)
+    }
+}
+
+impl From<f64> for PescNumber {
+    fn from(f: f64) -> Self {
+        PescNumber::Float(f)
+    }
+}
+
+impl From<usize> for PescNumber {
+    fn from(v: usize) -> Self {
+        PescNumber::Int(BigInt::from(v))
+    }
+}
+
+impl From<i64> for PescNumber {
+    fn from(v: i64) -> Self {
+        PescNumber::Int(BigInt::from(v))
+    }
+}
+
+impl Display for PescNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            PescNumber::Int(i) if f.precision().is_some() =>
+                Rational::from(i.clone()).fmt(f),
+            PescNumber::Int(i) => i.fmt(f),
+            PescNumber::Ratio(r) => r.fmt(f),
+            PescNumber::Float(v) => v.fmt(f),
+            PescNumber::Complex(c) => c.fmt(f),
+        }
+    }
+}
+
+impl PartialEq for PescNumber {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PescNumber::Complex(a), PescNumber::Complex(b)) => a == b,
+            _ => self.partial_cmp(other) == Some(Ordering::Equal),
+        }
+    }
+}
+
+impl PartialOrd for PescNumber {
+    // complex numbers aren't ordered
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (PescNumber::Complex(_), _)
+            | (_, PescNumber::Complex(_)) => None,
+
+            (PescNumber::Int(a), PescNumber::Int(b)) => Some(a.cmp(b)),
+            (PescNumber::Float(a), PescNumber::Float(b)) => a.partial_cmp(b),
+
+            // compare exactly if the float is integral, to avoid
+            // rounding large integers
+            (PescNumber::Int(a), PescNumber::Float(b)) => match BigInt::from_f64(*b) {
+                Some(b) => Some(a.cmp(&b)),
+                None => a.to_f64().partial_cmp(b),
+            },
+            (PescNumber::Float(_), PescNumber::Int(_)) =>
+                other.partial_cmp(self).map(|o| o.reverse()),
+
+            (PescNumber::Float(a), _) => a.partial_cmp(&other.to_f64()),
+            (_, PescNumber::Float(b)) => self.to_f64().partial_cmp(b),
+
+            // at least one rational, and no floats
+            _ => Some(self.to_rational().cmp(&other.to_rational())),
+        }
+    }
+}
+
+impl<'a> Add<&'a PescNumber> for &'a PescNumber {
+    type Output = PescNumber;
+
+    fn add(self, other: &PescNumber) -> PescNumber {
+        self.binop(other, |a, b| a + b, |a, b| Some(a + b), |a, b| Some(a + b),
+            |a, b| a + b)
+    }
+}
+
+impl<'a> Sub<&'a PescNumber> for &'a PescNumber {
+    type Output = PescNumber;
+
+    fn sub(self, other: &PescNumber) -> PescNumber {
+        self.binop(other, |a, b| a - b, |a, b| Some(a - b), |a, b| Some(a - b),
+            |a, b| a - b)
+    }
+}
+
+impl<'a> Mul<&'a PescNumber> for &'a PescNumber {
+    type Output = PescNumber;
+
+    fn mul(self, other: &PescNumber) -> PescNumber {
+        self.binop(other, |a, b| a * b, |a, b| Some(a * b), |a, b| Some(a * b),
+            |a, b| a * b)
+    }
+}
+
+impl<'a> Div<&'a PescNumber> for &'a PescNumber {
+    type Output = PescNumber;
+
+    // integer division only stays an integer if it's exact,
+    // otherwise we get a rational.
+    fn div(self, other: &PescNumber) -> PescNumber {
+        self.binop(other, |a, b| a / b, |a, b| match a.divrem(b) {
+            Some((q, r)) if r.is_zero() => Some(q),
+            _ => None,
+        }, |a, b| a / b, |a, b| a / b)
+    }
+}
+
+impl<'a> Rem<&'a PescNumber> for &'a PescNumber {
+    type Output = PescNumber;
+
+    fn rem(self, other: &PescNumber) -> PescNumber {
+        // a % b = a - b × trunc(a / b), same as for floats
+        self.binop(other, |a, b| {
+            let q = a / b;
+            a - b * Complex::new(q.re.trunc(), q.im.trunc())
+        }, |a, b| a.divrem(b).map(|(_, r)| r),
+            |a, b| (a / b).map(|q| a - &(b * &Rational::from(q.trunc()))),
+            |a, b| a % b)
+    }
+}
+
+impl Neg for PescNumber {
+    type Output = PescNumber;
+
+    fn neg(self) -> PescNumber {
+        match self {
+            PescNumber::Int(i) => PescNumber::Int(-i),
+            PescNumber::Ratio(r) => PescNumber::Ratio(-r),
+            PescNumber::Float(f) => PescNumber::Float(-f),
+            PescNumber::Complex(c) => PescNumber::Complex(-c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(s: &str) -> PescNumber {
+        PescNumber::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(matches!(num("15"), PescNumber::Int(_)));
+        assert!(matches!(num("1.5"), PescNumber::Ratio(_)));
+        assert!(matches!(num("2.0"), PescNumber::Int(_)));
+        assert!(matches!(num("-3"), PescNumber::Int(_)));
+        assert!(matches!(num("inf"), PescNumber::Float(_)));
+        assert_eq!(num("-2/6"), &num("-1") / &num("3"));
+        assert!(PescNumber::parse("1.2.3").is_none());
+        assert!(PescNumber::parse("1/0").is_none());
+
+        assert_eq!(num("0xFF"), num("255"));
+        assert_eq!(num("-0o17"), num("-15"));
+        assert_eq!(num("0b101"), num("5"));
+        assert!(PescNumber::parse("0b102").is_none());
+        assert!(PescNumber::parse("0x").is_none());
+        assert_eq!(num("6.02e23"), PescNumber::Float(6.02e23));
+        assert_eq!(num("1e-9"), PescNumber::Float(1e-9));
+    }
+
+    #[test]
+    fn test_promotion() {
+        assert!(matches!(&num("6") / &num("3"), PescNumber::Int(_)));
+        assert!(matches!(&num("1") / &num("3"), PescNumber::Ratio(_)));
+        assert_eq!(&num("1") / &num("2"), num("0.5"));
+        assert_eq!(&num("2") + &num("0.5"), num("2.5"));
+        assert_eq!(num("2").pow(&num("100")).to_string(),
+            "1267650600228229401496703205376");
+        assert_eq!(num("2").pow(&num("-1")), num("0.5"));
+        assert_eq!(num("2.5").floor(), num("2"));
+        assert!(matches!(&num("0.5") + &PescNumber::Float(0.5), PescNumber::Float(_)));
+
+        // results too big to compute exactly are approximated
+        assert!(matches!(num("2").pow(&num("20000000")), PescNumber::Float(_)));
+        assert!(matches!(num("1.5").pow(&num("-20000000")), PescNumber::Float(_)));
+        assert_eq!(num("-1").pow(&num("20000001")), num("-1"));
+        assert!(matches!(num("3").pow(&num("10000")), PescNumber::Int(_)));
+    }
+
+    #[test]
+    fn test_exact() {
+        assert_eq!((&num("0.1") + &num("0.2")).to_string(), "0.3");
+        assert_eq!((&num("1") / &num("3")).to_string(), "1/3");
+
+        let third = &num("1") / &num("3");
+        assert_eq!(&(&third + &third) + &third, num("1"));
+        assert_eq!(third.pow(&num("2")).to_string(), "1/9");
+        assert_eq!(third.pow(&num("-2")), num("9"));
+        assert_eq!((&num("7.5") % &num("2")).to_string(), "1.5");
+        assert_eq!((&num("-7") / &num("2")).round(), num("-4"));
+    }
+
+    #[test]
+    fn test_complex() {
+        assert_eq!(num("3+2i").to_string(), "3+2i");
+        assert_eq!(num("-1").sqrt(), num("1i"));
+        assert_eq!(&num("1i") * &num("1i"), num("-1"));
+        assert!(!(&num("1i") * &num("1i")).is_complex());
+        assert_eq!(&num("2") + &num("0.5i"), num("2+0.5i"));
+        assert!(num("-8").pow(&num("0.5")).is_complex());
+        assert!(num("-1").log(&num("10")).is_complex());
+        assert!(num("1i").partial_cmp(&num("1")).is_none());
+    }
+
+    #[test]
+    fn test_cmp() {
+        assert_eq!(num("1"), num("1.0"));
+        assert!(num("2") > num("1.5"));
+        assert!(num("1.5") < num("2"));
+        assert!(num("9007199254740993") > num("9007199254740992.0"));
+    }
+}
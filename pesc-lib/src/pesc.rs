@@ -1,7 +1,18 @@
+use std::mem;
 use std::rc::Rc;
 use std::fmt::{self, Display};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use crate::errors::*;
+use crate::bigint::BigInt;
+use crate::lexer::*;
+use crate::bytecode::*;
+use crate::check;
+use crate::pretty;
+use crate::info::FuncInfo;
+use crate::session::Session;
+
+pub use crate::number::PescNumber;
 
 /// This is a special, reserved operator that pushes (true) to the stack.
 pub const BOOLEAN_TRUE:  char = 'T';
@@ -9,24 +20,66 @@ pub const BOOLEAN_TRUE:  char = 'T';
 /// Same as BOOLEAN_TRUE, only it pushes (false) to the stack.
 pub const BOOLEAN_FALSE: char = 'F';
 
+/// How deeply macros, lists, and maps may be nested inside each other.
+/// Printing, comparing, and dropping values is recursive, so anything
+/// deeper could overflow Rust's stack.
+pub const MAX_NESTING: usize = 1000;
+
+/// Take a value out of an Rc, copying it if it's shared.
+pub(crate) fn unshare<T: Clone>(rc: Rc<T>) -> T {
+    Rc::try_unwrap(rc).unwrap_or_else(|rc| (*rc).clone())
+}
+
 #[derive(Clone, Debug, PartialEq)]
 /// A single Pesc token, which can be either the result of parsing
 /// some Pesc code, or be a value on the stack.
+///
+/// Strings, lists, and maps are shared through an Rc (and macros
+/// through Macro), so copying a value is cheap however big it is.
 pub enum PescToken {
-    Str(String),
+    Str(Rc<str>),
     Number(PescNumber),
     Func(String),
-    Macro(Vec<PescToken>),
+    Macro(Macro),
+    List(Rc<Vec<PescToken>>),
+    Map(Rc<BTreeMap<String, PescToken>>),
     Symbol(char),
     Bool(bool),
 }
 
+impl PescToken {
+    /// How many macros, lists, and maps deep this value goes (0 for
+    /// anything else).
+    pub fn nesting(&self) -> usize {
+        let deepest = |items: &mut dyn Iterator<Item = &PescToken>| {
+            items.map(|t| t.nesting()).max().unwrap_or(0) + 1
+        };
+
+        match self {
+            PescToken::Macro(m) => deepest(&mut m.iter()),
+            PescToken::List(l) => deepest(&mut l.iter()),
+            PescToken::Map(m) => deepest(&mut m.values()),
+            _ => 0,
+        }
+    }
+}
+
 impl Display for PescToken {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
-            PescToken::Macro(m) => write!(f, "<mac {:p}>", m),
+            PescToken::Macro(_) => write!(f, "{}", pretty::token(self)),
+            PescToken::List(l) => {
+                let items = l.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+                write!(f, "'({})", items.join(" "))
+            },
+            PescToken::Map(m) => {
+                let pairs = m.iter()
+                    .map(|(k, v)| format!("{} {}", pretty::string(k), v))
+                    .collect::<Vec<_>>();
+                write!(f, "'{{{}}}", pairs.join(" "))
+            },
             PescToken::Symbol(y) => write!(f, "<sym '{}'>", y),
-            PescToken::Str(s) => write!(f, "{:?}", s),
+            PescToken::Str(s) => write!(f, "{}", pretty::string(s)),
             PescToken::Number(n) => write!(f, "{}", n),
             PescToken::Func(s) => write!(f, "<fn {}>", s),
             PescToken::Bool(b) => write!(f, "({})", b),
@@ -34,27 +87,176 @@ impl Display for PescToken {
     }
 }
 
-pub type PescNumber = f64;
 pub type PescFunc = dyn Fn(&mut Pesc) -> Result<(), PescErrorType>;
 
+/// Something that takes the text printed by Pesc code (e.g. by
+/// [help]), one line at a time.
+pub type PescOutput = dyn FnMut(&str);
+
+/// The body of a function that can be called from Pesc code.
+#[derive(Clone)]
+pub enum PescFuncBody {
+    /// A function implemented in Rust.
+    Native(Rc<Box<PescFunc>>),
+
+    /// A function defined in Pesc (e.g. with [def]).
+    Macro(Rc<Chunk>),
+}
+
+/// Limits on how much work Pesc code may do, for when it comes from
+/// an untrusted source. A limit of None means there's no limit.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PescLimits {
+    /// The maximum number of tokens executed in one call to
+    /// Pesc::eval. Native functions that do a lot of work may count
+    /// extra steps (see Pesc::charge).
+    pub steps: Option<usize>,
+
+    /// The maximum depth of nested macro and function calls.
+    pub depth: Option<usize>,
+
+    /// The maximum length of the stack.
+    pub stack: Option<usize>,
+}
+
+// a function's name and body. compiled code refers to functions by
+// the index of their slot, so slots are never removed, and functions
+// that are called before they're defined get an empty slot.
+struct Slot {
+    name: String,
+    body: Option<PescFuncBody>,
+    info: Option<FuncInfo>,
+}
+
+// an entry in the undo journal, which is used to roll back changes
+// when a call fails (see Pesc::begin). only changes to values that were
+// on the stack before the call began need to be journaled; anything
+// above that is simply truncated away.
+enum Undo {
+    // a value was popped off from this index
+    Pop(usize, PescToken),
+
+    // the value at this index was overwritten
+    Set(usize, PescToken),
+
+    // the function in a slot was (re)defined
+    Def(usize, Option<PescFuncBody>),
+
+    // a value was pushed onto a register
+    RegPush(String),
+
+    // a value was popped off of a register
+    RegPop(String, PescToken),
+}
+
+// the state needed to roll back or commit a transaction.
+struct Transaction {
+    mark: usize,
+    low: Option<usize>,
+}
+
+// a copy of everything that [undo] can restore.
+struct Snapshot {
+    stack: Vec<PescToken>,
+    defs: Vec<Option<PescFuncBody>>,
+    registers: HashMap<String, Vec<PescToken>>,
+}
+
+// a chunk of code that's currently being executed, and the index of
+// the next token in it to execute.
+struct Frame {
+    chunk: Rc<Chunk>,
+    pc: usize,
+}
+
+// where Pesc::stamp comes from. each interpreter gets its own, so that
+// a macro compiled by one isn't run by another.
+static STAMPS: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Default)]
 pub struct Pesc {
+    /// The stack. Native functions should only change it with
+    /// Pesc::push, Pesc::pop, and Pesc::set, so that a failing call
+    /// can be rolled back.
     pub stack: Vec<PescToken>,
-    pub funcs: HashMap<String, Rc<Box<PescFunc>>>,
+
     pub ops: HashMap<char, String>,
+
+    /// Named alternative stacks, similar to dc's registers.
+    pub registers: HashMap<String, Vec<PescToken>>,
+
+    pub limits: PescLimits,
+
+    /// How many inputs back [undo] can go. This is 0 (no history) by
+    /// default, as keeping a history means copying the stack each time
+    /// Pesc::eval is called.
+    pub history_limit: usize,
+
+    /// Where text printed by Pesc code goes. If this is None, it's
+    /// written to stdout.
+    pub output: Option<Box<PescOutput>>,
+
+    // function names, and the index of their slot
+    funcs: HashMap<String, usize>,
+    slots: Vec<Slot>,
+
+    // what macros have been compiled under (see Macro). 0 means a new
+    // stamp is needed before anything is compiled again.
+    stamp: usize,
+
+    // the number of steps taken since Pesc::eval was called
+    steps: usize,
+
+    // the call stack. this lives on the heap, rather than in Rust's
+    // own call stack, so that deeply recursive Pesc code doesn't
+    // overflow anything.
+    frames: Vec<Frame>,
+
+    // code that the currently running native function wants to run
+    // after it returns (see Pesc::schedule)
+    pending: Vec<Frame>,
+
+    // the undo journal, and the lowest the stack has been since the
+    // innermost transaction began (or None if there isn't one)
+    journal: Vec<Undo>,
+    low: Option<usize>,
+
+    // the state before each of the last few inputs, the states that
+    // were undone, and the number of undos (if negative) or redos (if
+    // positive) that the current input asked for
+    history: VecDeque<Snapshot>,
+    future: Vec<Snapshot>,
+    travel: isize,
 }
 
 impl Pesc {
     pub fn new() -> Self {
         Self {
             stack: Vec::new(),
-            funcs: HashMap::new(),
             ops: HashMap::new(),
+            registers: HashMap::new(),
+            limits: PescLimits::default(),
+            history_limit: 0,
+            output: None,
+            funcs: HashMap::new(),
+            slots: Vec::new(),
+            stamp: 0,
+            steps: 0,
+            frames: Vec::new(),
+            pending: Vec::new(),
+            journal: Vec::new(),
+            low: None,
+            history: VecDeque::new(),
+            future: Vec::new(),
+            travel: 0,
         }
     }
 
-    /// "Load" a function in order to allow Pesc code to call it.
+    /// "Load" a function in order to allow Pesc code to call it, along
+    /// with a description of it (which is used by Pesc::check and in
+    /// error messages).
     pub fn load(&mut self, op: Option<char>, fnname: &str,
-        func: Rc<Box<PescFunc>>)
+        func: Rc<Box<PescFunc>>, info: FuncInfo)
     {
         if let Some(o) = op {
             assert!(!self.ops.contains_key(&o),
@@ -63,13 +265,139 @@ impl Pesc {
                 "cannot add operator {:?}: reserved keyword", op);
 
             self.ops.insert(o, String::from(fnname));
+
+            // macros compiled before now took this operator to be unknown
+            self.stamp = 0;
+        }
+
+        let slot = self.slot(fnname);
+        if self.slots[slot].body.is_none() {
+            self.slots[slot].body = Some(PescFuncBody::Native(func));
+            self.slots[slot].info = Some(info);
+        }
+    }
+
+    /// Define a function whose body is some Pesc code, replacing any
+    /// existing function with the same name.
+    pub fn define(&mut self, fnname: &str, body: Vec<PescToken>) {
+        let chunk = self.compile(body);
+        let slot = self.slot(fnname);
+        let old = self.slots[slot].body.replace(PescFuncBody::Macro(Rc::new(chunk)));
+
+        if self.low.is_some() {
+            self.journal.push(Undo::Def(slot, old));
+        }
+    }
+
+    /// Get the body of a function, if it's been loaded or defined.
+    pub fn func(&self, fnname: &str) -> Option<&PescFuncBody> {
+        let slot = *self.funcs.get(&fnname.to_lowercase())?;
+        self.slots[slot].body.as_ref()
+    }
+
+    /// The names of every function that's been loaded or defined, in
+    /// sorted order.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.slots.iter()
+            .filter(|s| s.body.is_some())
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// Print a line of text (see Pesc::output).
+    pub fn print(&mut self, line: &str) {
+        match &mut self.output {
+            Some(out) => out(line),
+            None => println!("{}", line),
+        }
+    }
+
+    /// Get the description of a function that was loaded with
+    /// Pesc::load. Functions defined in Pesc code don't have one.
+    pub fn info(&self, fnname: &str) -> Option<&FuncInfo> {
+        let slot = *self.funcs.get(&fnname.to_lowercase())?;
+        match self.slots[slot].body {
+            Some(PescFuncBody::Native(_)) => self.slots[slot].info.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Look for mistakes in some code without running it, starting
+    /// from the current stack. See check::check.
+    pub fn check(&self, code: &[PescToken], spans: &[TokenSpan]) -> Vec<PescError> {
+        check::check(self, code, spans)
+    }
+
+    /// Save the stack, functions defined with Pesc::define (or [def]),
+    /// and registers, so that they can be brought back later with
+    /// Pesc::restore.
+    pub fn save(&self) -> String {
+        let defs = self.slots.iter()
+            .filter_map(|s| match &s.body {
+                Some(PescFuncBody::Macro(c)) => Some((s.name.clone(), c.code.clone())),
+                _ => None,
+            })
+            .collect();
+
+        Session {
+            stack: self.stack.clone(),
+            defs,
+            registers: self.registers.clone(),
+        }.write()
+    }
+
+    /// Restore a session saved with Pesc::save, replacing the stack and
+    /// registers, and defining the saved functions. Nothing is changed
+    /// if the session can't be read.
+    pub fn restore(&mut self, session: &str) -> Result<(), PescError> {
+        let session = Session::read(session)?;
+
+        self.stack = session.stack;
+        self.registers = session.registers;
+
+        for (name, body) in session.defs {
+            self.define(&name, body);
         }
 
-        let s_fnname = String::from(fnname);
+        Ok(())
+    }
 
-        if !self.funcs.contains_key(&s_fnname) {
-            self.funcs.insert(s_fnname, func);
+    // get the slot of a function, creating an empty one if there
+    // isn't one yet.
+    fn slot(&mut self, fnname: &str) -> usize {
+        // most names are already lowercase, so try them as-is first
+        if let Some(&slot) = self.funcs.get(fnname) {
+            return slot;
         }
+
+        let name = fnname.to_lowercase();
+
+        if let Some(&slot) = self.funcs.get(&name) {
+            return slot;
+        }
+
+        self.slots.push(Slot { name: name.clone(), body: None, info: None });
+        self.funcs.insert(name, self.slots.len() - 1);
+        self.slots.len() - 1
+    }
+
+    /// Compile some Pesc tokens to bytecode, looking up the functions
+    /// and operators they call ahead of time.
+    pub fn compile(&mut self, code: Vec<PescToken>) -> Chunk {
+        let ops = code.iter()
+            .map(|t| match t {
+                PescToken::Func(f) => Op::Call(self.slot(f)),
+                PescToken::Symbol(o) => match self.ops.get(o).cloned() {
+                    Some(f) => Op::Call(self.slot(&f)),
+                    None => Op::Unknown,
+                },
+                _ => Op::Push,
+            })
+            .collect();
+
+        Chunk { code, ops }
     }
 
     /// Evaluate some Pesc tokens (that have already been parsed with
@@ -77,268 +405,582 @@ impl Pesc {
     pub fn eval(&mut self, code: &[PescToken])
         -> Result<(), (Vec<PescToken>, PescError)>
     {
-        for t in code {
-            match t {
+        self.eval_spanned(code, &[])
+    }
+
+    /// The same as Pesc::eval, but errors are given the span of the
+    /// token that failed, taken from the spans returned by Pesc::parse.
+    pub fn eval_spanned(&mut self, code: &[PescToken], spans: &[TokenSpan])
+        -> Result<(), (Vec<PescToken>, PescError)>
+    {
+        let before = self.checkpoint();
+        let res = self.eval_tokens(code, spans);
+
+        self.record(before, res.is_ok(), true);
+        res
+    }
+
+    /// The same as Pesc::eval_spanned, but all-or-nothing: if anything
+    /// fails, the stack, function definitions, and registers are all
+    /// restored to their state before the call, rather than just
+    /// undoing the token that failed.
+    ///
+    /// The stack at the time of the failure is still returned along
+    /// with the error.
+    pub fn eval_atomic(&mut self, code: &[PescToken], spans: &[TokenSpan])
+        -> Result<(), (Vec<PescToken>, PescError)>
+    {
+        let before = self.checkpoint();
+        let txn = self.begin();
+        let res = self.eval_tokens(code, spans);
+
+        match res {
+            Ok(()) => self.commit(txn),
+            Err(_) => self.rollback(txn),
+        }
+
+        self.record(before, res.is_ok(), res.is_ok());
+        res
+    }
+
+    fn eval_tokens(&mut self, code: &[PescToken], spans: &[TokenSpan])
+        -> Result<(), (Vec<PescToken>, PescError)>
+    {
+        self.steps = 0;
+
+        for (i, t) in code.iter().enumerate() {
+            let res = match t {
                 PescToken::Symbol(_)
                 | PescToken::Func(_) => {
-                    match self.exec(t.clone()) {
-                        Ok(()) => (),
-                        Err((b, e)) => return Err((b,
-                            PescError::new(None, Some(t.clone()), e))),
-                    };
+                    let chunk = self.compile(vec![t.clone()]);
+                    self.exec(Rc::new(chunk))
+                },
+                _ => {
+                    self.stack.push(t.clone());
+                    self.step().map_err(|e| {
+                        let badstack = self.stack.clone();
+                        self.stack.pop();
+                        (badstack, e)
+                    })
                 },
-                _ => self.stack.push(t.clone()),
+            };
+
+            if let Err((b, e)) = res {
+                return Err((b, PescError::new(
+                    spans.get(i).map(|s| s.span), Some(t.clone()), e)));
             }
         }
 
         Ok(())
     }
 
+    /// Go back to the state (the stack, function definitions, and
+    /// registers) before the last input given to Pesc::eval, returning
+    /// false if there's nothing to undo.
+    ///
+    /// If this is called while Pesc code is running (e.g. by [undo]),
+    /// it takes effect once the input has finished, and replaces
+    /// whatever else the input did.
+    pub fn undo(&mut self) -> bool {
+        if self.low.is_some() {
+            if self.history.len() as isize + self.travel <= 0 {
+                return false;
+            }
+
+            self.travel -= 1;
+            return true;
+        }
+
+        match self.history.pop_back() {
+            Some(s) => {
+                let now = self.snapshot();
+                self.future.push(now);
+                self.rewind(s);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Redo an input that was undone with Pesc::undo, returning false
+    /// if there's nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if self.low.is_some() {
+            if self.future.len() as isize - self.travel <= 0 {
+                return false;
+            }
+
+            self.travel += 1;
+            return true;
+        }
+
+        match self.future.pop() {
+            Some(s) => {
+                let now = self.snapshot();
+                self.history.push_back(now);
+                self.rewind(s);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // take a snapshot of the state before an input, if history is on.
+    fn checkpoint(&mut self) -> Option<Snapshot> {
+        self.travel = 0;
+
+        if self.history_limit > 0 {
+            Some(self.snapshot())
+        } else {
+            None
+        }
+    }
+
+    // add the state before an input to the history, and carry out any
+    // undos or redos it asked for.
+    fn record(&mut self, before: Option<Snapshot>, succeeded: bool, changed: bool) {
+        let before = match before {
+            Some(b) => b,
+            None => return,
+        };
+
+        let travel = mem::replace(&mut self.travel, 0);
+
+        if succeeded && travel != 0 {
+            self.rewind(before);
+
+            for _ in travel..0 {
+                self.undo();
+            }
+
+            for _ in 0..travel {
+                self.redo();
+            }
+        } else if changed {
+            self.history.push_back(before);
+            self.future.clear();
+
+            while self.history.len() > self.history_limit {
+                self.history.pop_front();
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            stack: self.stack.clone(),
+            defs: self.slots.iter().map(|s| s.body.clone()).collect(),
+            registers: self.registers.clone(),
+        }
+    }
+
+    fn rewind(&mut self, s: Snapshot) {
+        self.stack = s.stack;
+        self.registers = s.registers;
+
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            // functions loaded after the snapshot was taken stay
+            match (s.defs.get(i), &slot.body) {
+                (None, Some(PescFuncBody::Native(_))) => (),
+                (def, _) => slot.body = def.cloned().flatten(),
+            }
+        }
+    }
+
     /// Execute a Pesc macro or a Pesc function, returning an error
     /// in the case of failure.
     pub fn try_exec(&mut self, tok: PescToken) -> Result<(), PescErrorType> {
-        match self.exec(tok) {
+        let chunk = self.compile_callable(tok)?;
+
+        match self.exec(chunk) {
             Ok(()) => Ok(()),
             Err((_, e)) => Err(e),
         }
     }
 
-    /// The same as Pesc::try_exec, but return a copy of the Pesc stack
-    /// at the time of the failure as well as an error.
-    fn exec(&mut self, tok: PescToken)
-        -> Result<(), (Vec<PescToken>, PescErrorType)>
-    {
+    /// Execute a Pesc macro or function and return the value it leaves
+    /// on top of the stack. Everything else it did is then undone, so
+    /// that, like the conditions of Joy's combinators, it doesn't
+    /// disturb the stack.
+    pub fn try_peek(&mut self, tok: PescToken) -> Result<PescToken, PescErrorType> {
+        let txn = self.begin();
+        let res = self.try_exec(tok).and_then(|()| self.pop());
+        self.rollback(txn);
+        res
+    }
+
+    /// Arrange for a Pesc macro or function to be executed once the
+    /// currently running native function returns, as if it were called
+    /// at the end of the native function.
+    ///
+    /// Unlike Pesc::try_exec, this doesn't grow Rust's call stack, so
+    /// functions like [run] and [if?] use it to allow unbounded
+    /// recursion. If it's called several times, the scheduled code is
+    /// executed in the same order.
+    pub fn schedule(&mut self, tok: PescToken) -> Result<(), PescErrorType> {
+        let chunk = self.compile_callable(tok)?;
+        self.pending.push(Frame { chunk, pc: 0 });
+        Ok(())
+    }
+
+    // compile a macro, or a call to a single function. macros keep
+    // their compiled chunk, so this is only done once for each.
+    fn compile_callable(&mut self, tok: PescToken) -> Result<Rc<Chunk>, PescErrorType> {
         match tok {
-            PescToken::Symbol(o) => {
-                if !self.ops.contains_key(&o) {
-                    return Err((self.stack.clone(),
-                        PescErrorType::UnknownFunction(format!("'{}'", o))));
+            PescToken::Macro(m) => {
+                if self.stamp == 0 {
+                    self.stamp = STAMPS.fetch_add(1, Ordering::Relaxed);
                 }
 
-                self.exec(PescToken::Func(self.ops[&o].clone()))
-            },
-            PescToken::Func(_func) => {
-                let func = _func.to_lowercase();
-                if !self.funcs.contains_key(&func) {
-                    return Err((self.stack.clone(),
-                        PescErrorType::UnknownFunction(func)));
+                if let Some(chunk) = m.compiled(self.stamp) {
+                    return Ok(chunk);
                 }
 
-                let backup = self.stack.clone();
-                match (&self.funcs.clone()[&func])(self) {
-                    Ok(()) => Ok(()),
-                    Err(e) => {
-                        let badstack = self.stack.clone();
-                        self.stack = backup;
-                        Err((badstack, e))
-                    },
+                let chunk = Rc::new(self.compile(m.to_vec()));
+                m.set_compiled(self.stamp, chunk.clone());
+                Ok(chunk)
+            },
+            PescToken::Symbol(_)
+            | PescToken::Func(_) => Ok(Rc::new(self.compile(vec![tok]))),
+            _ => Err(PescErrorType::InvalidArgumentType(
+                String::from("macro/function"), tok.to_string())),
+        }
+    }
+
+    // execute some code, returning a copy of the Pesc stack at the
+    // time of the failure as well as an error. The stack is restored
+    // to its state before the call.
+    fn exec(&mut self, chunk: Rc<Chunk>)
+        -> Result<(), (Vec<PescToken>, PescErrorType)>
+    {
+        let base = self.frames.len();
+        let txn = self.begin();
+
+        match self.run(chunk, base) {
+            Ok(()) => {
+                self.commit(txn);
+                Ok(())
+            },
+            Err(e) => {
+                self.frames.truncate(base);
+                let badstack = self.stack.clone();
+                self.rollback(txn);
+                Err((badstack, e))
+            },
+        }
+    }
+
+    // start journaling changes, so that they can be rolled back.
+    // transactions may be nested.
+    fn begin(&mut self) -> Transaction {
+        let txn = Transaction { mark: self.journal.len(), low: self.low };
+        self.low = Some(self.stack.len());
+        txn
+    }
+
+    // undo everything since the transaction began.
+    fn rollback(&mut self, txn: Transaction) {
+        if let Some(low) = self.low {
+            self.stack.truncate(low);
+        }
+
+        for undo in self.journal.drain(txn.mark..).rev() {
+            match undo {
+                Undo::Pop(_, v) => self.stack.push(v),
+                Undo::Set(i, v) => self.stack[i] = v,
+                Undo::Def(slot, body) => self.slots[slot].body = body,
+                Undo::RegPush(r) => {
+                    self.registers.get_mut(&r).and_then(|r| r.pop());
+                },
+                Undo::RegPop(r, v) => {
+                    self.registers.entry(r).or_default().push(v);
+                },
+            }
+        }
+
+        self.low = txn.low;
+    }
+
+    // keep everything since the transaction began.
+    fn commit(&mut self, txn: Transaction) {
+        let outer = match txn.low {
+            Some(l) => l,
+            None => {
+                // nothing left that could be rolled back
+                self.journal.clear();
+                self.low = None;
+                return;
+            },
+        };
+
+        // the enclosing transaction only needs to know about the values
+        // that were on the stack before *it* began; the rest would be
+        // truncated away anyway.
+        let inner = self.journal.split_off(txn.mark);
+        self.journal.extend(inner.into_iter().filter(|u| match u {
+            Undo::Pop(j, _)
+            | Undo::Set(j, _) => *j < outer,
+            _ => true,
+        }));
+
+        self.low = self.low.map(|l| l.min(outer));
+    }
+
+    // execute a chunk and everything it calls, until the call stack
+    // is back down to `base` frames.
+    fn run(&mut self, chunk: Rc<Chunk>, base: usize) -> Result<(), PescErrorType> {
+        if !chunk.is_empty() {
+            self.frames.push(Frame { chunk, pc: 0 });
+        }
+
+        while self.frames.len() > base {
+            let frame = self.frames.last_mut().unwrap();
+            let pc = frame.pc;
+            let op = frame.chunk.ops[pc];
+            frame.pc += 1;
+
+            let tok = match op {
+                Op::Call(_) => None,
+                _ => Some(frame.chunk.code[pc].clone()),
+            };
+
+            // tail-call elimination: drop the frame before executing
+            // its last token, so that tail-recursive code runs in
+            // constant space.
+            if frame.pc >= frame.chunk.len() {
+                self.frames.pop();
+            }
+
+            match (op, tok) {
+                (Op::Call(slot), _) => self.call(slot)?,
+                (Op::Push, Some(t)) => self.stack.push(t),
+                (_, Some(PescToken::Symbol(o))) => return Err(
+                    PescErrorType::UnknownFunction(format!("'{}'", o))),
+                _ => unreachable!(),
+            }
+
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// Count some extra steps towards the step limit, returning an
+    /// error if it's exceeded. Native functions that may run for a
+    /// long time should call this as they go.
+    pub fn charge(&mut self, steps: usize) -> Result<(), PescErrorType> {
+        self.steps = self.steps.saturating_add(steps);
+
+        match self.limits.steps {
+            Some(max) if self.steps > max => Err(PescErrorType::LimitExceeded(
+                String::from("step"), max)),
+            _ => Ok(()),
+        }
+    }
+
+    // count a step, and check that we're within all the limits.
+    fn step(&mut self) -> Result<(), PescErrorType> {
+        self.charge(1)?;
+
+        if let Some(max) = self.limits.depth {
+            if self.frames.len() > max {
+                return Err(PescErrorType::LimitExceeded(
+                    String::from("call depth"), max));
+            }
+        }
+
+        if let Some(max) = self.limits.stack {
+            if self.stack.len() > max {
+                return Err(PescErrorType::LimitExceeded(
+                    String::from("stack size"), max));
+            }
+        }
+
+        Ok(())
+    }
+
+    // call the function in a slot. functions defined in Pesc get a new
+    // frame, which the caller (Pesc::run) will then execute.
+    fn call(&mut self, slot: usize) -> Result<(), PescErrorType> {
+        let body = match &self.slots[slot].body {
+            Some(b) => b.clone(),
+            None => return Err(PescErrorType::UnknownFunction(
+                self.slots[slot].name.clone())),
+        };
+
+        match body {
+            PescFuncBody::Native(f) => {
+                let given = self.stack.len();
+                let outer = mem::take(&mut self.pending);
+                let res = f(self);
+                let pending = mem::replace(&mut self.pending, outer);
+
+                if let Err(PescErrorType::NotEnoughArguments(None)) = res {
+                    return Err(self.underflow(slot, given));
                 }
+                res?;
+                self.frames.extend(pending.into_iter().rev()
+                    .filter(|f| !f.chunk.is_empty()));
             },
-            PescToken::Macro(mac) => match self.eval(&mac) {
-                Ok(()) => Ok(()),
-                Err((b, e)) => Err((b, e.kind)),
+            PescFuncBody::Macro(chunk) => if !chunk.is_empty() {
+                self.frames.push(Frame { chunk, pc: 0 });
             },
-            _ => Err((self.stack.clone(), PescErrorType::InvalidArgumentType(
-                String::from("macro/function"), tok.to_string())))
         }
+
+        Ok(())
+    }
+
+    // say how many arguments a function wanted, if we know.
+    fn underflow(&self, slot: usize, given: usize) -> PescErrorType {
+        let needed = match &self.slots[slot].info {
+            Some(info) if !info.sig.more_inputs => info.arity(),
+            _ => 0,
+        };
+
+        PescErrorType::NotEnoughArguments(match needed > given {
+            true => Some((self.slots[slot].name.clone(), needed, given)),
+            false => None,
+        })
     }
 
-    // TODO: cleanup, remove duplicated code
-    // here be atrocious code
     /// Parse some input into a vector of Pesc tokens.
-    /// Note that the first element in the tuple (that is, the usize)
-    /// can be safely discarded.
+    /// The first element in the tuple holds the source span of each
+    /// token, and can be safely discarded if it isn't needed.
     ///
     /// # Examples
     ///
     /// ```
-    /// let pesc = Pesc::new();
+    /// use pesc::pesc::Pesc;
+    /// use pesc::stdlib;
+    ///
+    /// let mut pesc = Pesc::new();
+    /// for func in stdlib::standard().into_iter().chain(stdlib::extended()) {
+    ///     pesc.load(func.0, func.1, func.2, func.3);
+    /// }
     ///
     /// let stuff = "1 1 + \\ 2 + [ack]";
-    /// let parsed = (Pesc::parse(stuff)).1;
+    /// let parsed = Pesc::parse(stuff).unwrap().1;
     ///
-    /// pesc.eval(parsed);
+    /// pesc.eval(&parsed).unwrap();
     ///
     /// // print the stack
     /// pesc.stack.iter().rev().for_each(|i| println!("{}", i));
     /// ```
-    pub fn parse(input: &str) -> Result<(usize, Vec<PescToken>), PescError> {
+    pub fn parse(input: &str)
+        -> Result<(Vec<TokenSpan>, Vec<PescToken>), PescError>
+    {
+        let mut spans = Vec::new();
         let mut toks = Vec::new();
 
-        let chs = input.chars()
-            .collect::<Vec<char>>();
-        let mut i = 0;
-
-        // chomp the chars, placing them in a buffer.
-        // return (String, usize, bool) when until() -> true or
-        // when it reaches the end of the chars
-        //
-        //     String = the buffer,
-        //     usize  = the end index,
-        //     bool   = did we reach the end of the data
-        //              without having until() return true?
-        //
-        fn chomp<F>(ch: &[char], mut c: usize, until: F)
-            -> (String, usize, bool)
-        where
-            F: Fn(char) -> bool
-        {
-            let mut buf = String::new();
-            let early_return;
-
-            loop {
-                if c >= ch.len() {
-                    early_return = true;
-                    break;
-                }
-
-                if until(ch[c]) == true {
-                    early_return = false;
-                    break;
-                }
+        // the macros and lists we're currently inside of (innermost
+        // last), along with the tokens parsed before each was opened
+        let mut open: Vec<(LexemeKind, Span, Vec<TokenSpan>, Vec<PescToken>)> = Vec::new();
 
-                buf += &format!("{}", ch[c]);
-                c += 1;
+        fn number(lit: String, span: Span) -> Result<PescToken, PescError> {
+            if lit.is_empty() {
+                return Err(PescError::new(Some(span), None,
+                    PescErrorType::EmptyLiteral));
             }
 
-            (buf, c, early_return)
+            match PescNumber::parse_literal(&lit) {
+                Some(n) => Ok(PescToken::Number(n)),
+                None => Err(PescError::new(Some(span), None,
+                    PescErrorType::InvalidNumberLit(lit))),
+            }
         }
 
-        while i < chs.len() {
-            match chs[i] {
-                // integer literals
-                _ if chs[i].is_numeric() || chs[i] == '.'
-                                         || chs[i] == '_' => {
-                    let mut sign = 1_f64;
-
-                    let n = chomp(&chs, i, |c| {
-                        !c.is_digit(10) && c != '_' && c != '.'
-                    });
-                    i = n.1;
+        // the characters that open and close a macro, list, or map.
+        fn delims(start: &LexemeKind) -> (char, char) {
+            match start {
+                LexemeKind::ListStart => ('(', ')'),
+                _ => ('{', '}'),
+            }
+        }
 
-                    if n.0.chars().nth(0).unwrap() == '_' {
-                        sign = -1_f64;
-                    }
+        // pair up the keys and values of a map literal.
+        fn map(body: Vec<PescToken>, spans: &[TokenSpan]) -> Result<PescToken, PescError> {
+            let mut map = BTreeMap::new();
+            let mut pairs = body.into_iter().zip(spans);
 
-                    let num = match n.0.replace("_", "").parse::<PescNumber>() {
-                        Ok(o) => o,
-                        Err(_) => return Err(PescError::new(Some(i), None,
-                            PescErrorType::InvalidNumberLit(n.0)))
-                    };
+            while let Some((key, kspan)) = pairs.next() {
+                let key = match key {
+                    PescToken::Str(k) => k.to_string(),
+                    _ => return Err(PescError::new(Some(kspan.span), None,
+                        PescErrorType::InvalidArgumentType(
+                            String::from("string"), key.to_string()))),
+                };
 
-                    toks.push(PescToken::Number(num * sign));
-                },
+                match pairs.next() {
+                    Some((value, _)) => map.insert(key, value),
+                    None => return Err(PescError::new(Some(kspan.span), None,
+                        PescErrorType::MissingValue(key))),
+                };
+            }
 
-                '(' => {
-                    let mut sign = 1_f64;
+            Ok(PescToken::Map(Rc::new(map)))
+        }
 
-                    let n = chomp(&chs, i + 1, |c| c == ')');
-                    i = n.1 + 1;
+        for lexeme in Lexer::new(input) {
+            let Lexeme { kind, mut span } = lexeme?;
+            let mut inner = Vec::new();
 
-                    if n.2 {
-                        // we hit the end of the data
-                        // without finding a matching bracket
-                        return Err(PescError::new(Some(i), None,
-                            PescErrorType::UnmatchedToken('(')));
-                    }
+            let tok = match kind {
+                LexemeKind::Number(n)
+                | LexemeKind::Literal(n) => number(n, span)?,
+                LexemeKind::Str(s) => PescToken::Str(s.into()),
+                LexemeKind::Func(f) => PescToken::Func(f),
+                LexemeKind::Bool(b) => PescToken::Bool(b),
+                LexemeKind::Symbol(c) => PescToken::Symbol(c),
 
-                    if n.0.len() == 0 {
-                        return Err(PescError::new(Some(i), None,
-                            PescErrorType::EmptyLiteral));
-                    } else {
-                        if n.0.chars().nth(0).unwrap() == '_' {
-                            // we don't need to check for a '-' prefix,
-                            // as the parse::<f64>() function will detect
-                            // that for us
-                            sign = -1_f64;
-                        }
+                LexemeKind::MacroStart
+                | LexemeKind::ListStart
+                | LexemeKind::MapStart => {
+                    if open.len() >= MAX_NESTING {
+                        return Err(PescError::new(Some(span), None,
+                            PescErrorType::LimitExceeded(String::from("nesting"), MAX_NESTING)));
                     }
 
-                    let num = match n.0.replace("_", "").parse::<PescNumber>() {
-                        Ok(o) => o,
-                        Err(_) => return Err(PescError::new(Some(i), None,
-                            PescErrorType::InvalidNumberLit(n.0)))
-                    };
-
-                    toks.push(PescToken::Number(num * sign));
+                    open.push((kind, span, mem::take(&mut spans), mem::take(&mut toks)));
+                    continue;
                 },
+                LexemeKind::MacroEnd
+                | LexemeKind::ListEnd => {
+                    let closer = if kind == LexemeKind::MacroEnd { '}' } else { ')' };
 
-                // strings
-                '"' => {
-                    let s = chomp(&chs, i + 1, |c| c == '"');
-                    i = s.1 + 1;
-
-                    if s.2 {
-                        // we hit the end of the data
-                        // without finding a matching quote
-                        return Err(PescError::new(Some(i), None,
-                            PescErrorType::UnmatchedToken('"')));
-                    }
-
-                    toks.push(PescToken::Str(s.0));
-                },
+                    let (opener, start, outer_spans, outer_toks) = match open.pop() {
+                        Some(o) if delims(&o.0).1 == closer => o,
+                        _ => return Err(PescError::new(Some(span), None,
+                            PescErrorType::UnmatchedToken(closer))),
+                    };
 
-                // functions
-                '[' => {
-                    let s = chomp(&chs, i + 1, |c| c == ']');
-                    i = s.1 + 1;
+                    span = Span { end: span.end, ..start };
+                    inner = mem::replace(&mut spans, outer_spans);
+                    let body = mem::replace(&mut toks, outer_toks);
 
-                    if s.2 {
-                        // we hit the end of the data
-                        // without finding a matching bracket
-                        return Err(PescError::new(Some(i), None,
-                            PescErrorType::UnmatchedToken('[')));
+                    match opener {
+                        LexemeKind::MacroStart => PescToken::Macro(body.into()),
+                        LexemeKind::ListStart => PescToken::List(Rc::new(body)),
+                        _ => map(body, &inner)?,
                     }
-
-                    toks.push(PescToken::Func(s.0));
                 },
+            };
 
-                // macros
-                '{' => {
-                    let res = Pesc::parse(&input[i + 1..])?;
-                    toks.push(PescToken::Macro(res.1));
-
-                    // move pointer past matching '}', or we
-                    // will exit prematurely (see next item)
-                    i += res.0 + 2;
-                },
-
-                '}' => return Ok((i, toks)),
-
-                // whitespace
-                '\n'
-                | '\t'
-                | ' ' => { i += 1; continue; },
-
-                // comments
-                '#' =>
-                    i = chomp(&chs, i + 1, |c| c == '\n' || c == '\\').1 + 1,
-
-                // boolean values
-                BOOLEAN_TRUE => {
-                    toks.push(PescToken::Bool(true));
-                    i += 1;
-                },
-
-                BOOLEAN_FALSE => {
-                    toks.push(PescToken::Bool(false));
-                    i += 1;
-                },
+            spans.push(TokenSpan { span, inner });
+            toks.push(tok);
+        }
 
-                // treat unknown characters as symbols aka operators
-                _ => {
-                    toks.push(PescToken::Symbol(chs[i]));
-                    i += 1;
-                }
-            }
+        if let Some((opener, span, _, _)) = open.pop() {
+            let span = Span { end: input.len(), ..span };
+            return Err(PescError::new(Some(span), None,
+                PescErrorType::UnmatchedToken(delims(&opener).0)));
         }
 
-        Ok((i, toks))
+        Ok((spans, toks))
     }
 
     /// Get a reference to the nth item in the Pesc stack.
     pub fn nth_ref(&self, i: PescNumber) -> Result<&PescToken, PescErrorType> {
-        match self.stack.iter().rev().nth(i as usize) {
+        match i.to_usize().and_then(|n| self.stack.iter().rev().nth(n)) {
             Some(value) => Ok(value),
             None => Err(PescErrorType::OutOfBounds(i, self.stack.len())),
         }
@@ -347,11 +989,18 @@ impl Pesc {
     /// Set the nth value in the Pesc stack to a value.
     pub fn set(&mut self, i: PescNumber, v: PescToken) -> Result<(), PescErrorType> {
         let len = self.stack.len();
-        if len <= i as usize {
-            Err(PescErrorType::OutOfBounds(i, self.stack.len()))
-        } else {
-            self.stack[(len - 1) - (i as usize)] = v;
-            Ok(())
+        match i.to_usize() {
+            Some(n) if n < len => {
+                let i = (len - 1) - n;
+                let old = mem::replace(&mut self.stack[i], v);
+
+                if matches!(self.low, Some(low) if i < low) {
+                    self.journal.push(Undo::Set(i, old));
+                }
+
+                Ok(())
+            },
+            _ => Err(PescErrorType::OutOfBounds(i, len)),
         }
     }
 
@@ -360,11 +1009,76 @@ impl Pesc {
         self.stack.push(v)
     }
 
+    /// Push a value, counting a step for it and checking that the stack
+    /// stays within its limit. Native functions that may push any
+    /// number of values should use this rather than Pesc::push.
+    pub fn push_limited(&mut self, v: PescToken) -> Result<(), PescErrorType> {
+        self.charge(1)?;
+
+        if let Some(max) = self.limits.stack {
+            if self.stack.len() >= max {
+                return Err(PescErrorType::LimitExceeded(
+                    String::from("stack size"), max));
+            }
+        }
+
+        self.push(v);
+        Ok(())
+    }
+
     /// Pop the first item on the stack.
     pub fn pop(&mut self) -> Result<PescToken, PescErrorType> {
-        match self.stack.pop() {
+        let v = match self.stack.pop() {
+            Some(value) => value,
+            None => return Err(PescErrorType::NotEnoughArguments(None))
+        };
+
+        if matches!(self.low, Some(low) if self.stack.len() < low) {
+            self.low = Some(self.stack.len());
+            // this is cheap, as strings, lists, and maps are shared
+            self.journal.push(Undo::Pop(self.stack.len(), v.clone()));
+        }
+
+        Ok(v)
+    }
+
+    /// Push a value onto a register, creating it if necessary.
+    /// (Like dc's S command.)
+    pub fn reg_push(&mut self, reg: &str, v: PescToken) {
+        self.registers.entry(String::from(reg)).or_default().push(v);
+
+        if self.low.is_some() {
+            self.journal.push(Undo::RegPush(String::from(reg)));
+        }
+    }
+
+    /// Pop the top value off of a register. (Like dc's L command.)
+    pub fn reg_pop(&mut self, reg: &str) -> Result<PescToken, PescErrorType> {
+        let v = match self.registers.get_mut(reg).and_then(|r| r.pop()) {
+            Some(value) => value,
+            None => return Err(PescErrorType::EmptyRegister(String::from(reg))),
+        };
+
+        if self.low.is_some() {
+            self.journal.push(Undo::RegPop(String::from(reg), v.clone()));
+        }
+
+        Ok(v)
+    }
+
+    /// Replace the top value of a register, or push it if the
+    /// register is empty. (Like dc's s command.)
+    pub fn reg_store(&mut self, reg: &str, v: PescToken) {
+        let _ = self.reg_pop(reg);
+        self.reg_push(reg, v);
+    }
+
+    /// Get a reference to the top value of a register. (Like dc's
+    /// l command.)
+    pub fn reg_load(&self, reg: &str) -> Result<&PescToken, PescErrorType> {
+        match self.registers.get(reg).and_then(|r| r.last()) {
             Some(value) => Ok(value),
-            None => Err(PescErrorType::NotEnoughArguments)
+            None => Err(PescErrorType::EmptyRegister(String::from(reg))),
         }
     }
 
@@ -383,13 +1097,40 @@ impl Pesc {
         }
     }
 
+    /// Pop the first value off of the stack, returning an error if the
+    /// value is not a real (i.e., non-complex) number.
+    pub fn pop_real(&mut self) -> Result<PescNumber, PescErrorType> {
+        let v = self.pop()?;
+
+        match v {
+            PescToken::Number(n) if !n.is_complex() => Ok(n),
+            _ => Err(PescErrorType::InvalidArgumentType(
+                String::from("real number"), v.to_string())),
+        }
+    }
+
+    /// Pop the first value off of the stack, returning an error if the
+    /// value is not an integer (or a float without a fractional part).
+    pub fn pop_integer(&mut self) -> Result<BigInt, PescErrorType> {
+        let v = self.pop()?;
+
+        if let PescToken::Number(n) = &v {
+            if let Some(i) = n.to_int() {
+                return Ok(i);
+            }
+        }
+
+        Err(PescErrorType::InvalidArgumentType(
+            String::from("integer"), v.to_string()))
+    }
+
     /// Pop the first value off of the stack, returning an error if the
     /// value is not a string.
     pub fn pop_string(&mut self) -> Result<String, PescErrorType> {
         let v = self.pop()?;
 
         if let PescToken::Str(n) = v {
-            Ok(n)
+            Ok(n.to_string())
         } else {
             Err(PescErrorType::InvalidArgumentType(
                 String::from("string"), v.to_string()))
@@ -402,31 +1143,586 @@ impl Pesc {
         let v = self.pop()?;
 
         if let PescToken::Macro(m) = v {
-            Ok(m)
+            Ok(m.into_code())
         } else {
             Err(PescErrorType::InvalidArgumentType(
                 String::from("macro"), v.to_string()))
         }
     }
 
+    /// Pop the first value off of the stack, returning an error if the
+    /// value is not a list.
+    pub fn pop_list(&mut self) -> Result<Vec<PescToken>, PescErrorType> {
+        let v = self.pop()?;
+
+        if let PescToken::List(l) = v {
+            Ok(unshare(l))
+        } else {
+            Err(PescErrorType::InvalidArgumentType(
+                String::from("list"), v.to_string()))
+        }
+    }
+
+    /// Pop the first value off of the stack, returning an error if the
+    /// value is not a map.
+    pub fn pop_map(&mut self) -> Result<BTreeMap<String, PescToken>, PescErrorType> {
+        let v = self.pop()?;
+
+        if let PescToken::Map(m) = v {
+            Ok(unshare(m))
+        } else {
+            Err(PescErrorType::InvalidArgumentType(
+                String::from("map"), v.to_string()))
+        }
+    }
+
     /// Pop the first value off of the stack, returning an error if the
     /// value is not a boolean value (i.e., is not a string, number, or
     /// bool)
     pub fn pop_boolean(&mut self) -> Result<bool, PescErrorType> {
         let v = self.pop()?;
         match v {
-            PescToken::Str(s) => if s == String::from("") {
-                Ok(false)
-            } else {
-                Ok(true)
-            },
-            PescToken::Number(n) => if n == 0.0 {
-                Ok(false)
-            } else {
-                Ok(true)
-            },
+            PescToken::Str(s) => Ok(!s.is_empty()),
+            PescToken::Number(n) => Ok(!n.is_zero()),
             PescToken::Bool(b) => Ok(b),
             _ => Err(PescErrorType::InvalidBoolean(v))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib;
+    use crate::info::Category;
+    use std::cell::RefCell;
+
+    fn load() -> Pesc {
+        let mut pesc = Pesc::new();
+        for func in stdlib::standard().into_iter().chain(stdlib::extended()) {
+            pesc.load(func.0, func.1, func.2, func.3);
+        }
+
+        // push whether a macro succeeded, using Pesc::try_exec
+        pesc.load(None, "try", Rc::new(Box::new(|p| {
+            let m = p.pop()?;
+            let ok = p.try_exec(m).is_ok();
+            p.push(PescToken::Bool(ok));
+            Ok(())
+        })), FuncInfo::new(Category::Misc, "f:callable -- bool", "whether f succeeds"));
+
+        pesc
+    }
+
+    fn run(input: &str) -> Result<Pesc, PescError> {
+        let mut pesc = load();
+        let parsed = Pesc::parse(input)?.1;
+        pesc.eval(&parsed).map_err(|(_, e)| e)?;
+        Ok(pesc)
+    }
+
+    // the lines printed by some code.
+    fn printed(input: &str) -> Vec<String> {
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let out = lines.clone();
+
+        let mut pesc = load();
+        pesc.output = Some(Box::new(move |l| out.borrow_mut().push(l.to_string())));
+        pesc.eval(&Pesc::parse(input).unwrap().1).unwrap();
+
+        let lines = lines.borrow().clone();
+        lines
+    }
+
+    #[test]
+    fn test_parse() {
+        let (spans, toks) = Pesc::parse("2 ×{ø { 1 } }").unwrap();
+        assert_eq!(toks, vec![
+            PescToken::Number(PescNumber::from(2_usize)),
+            PescToken::Symbol('×'),
+            PescToken::Macro(Macro::new(vec![
+                PescToken::Symbol('ø'),
+                PescToken::Macro(Macro::new(vec![PescToken::Number(PescNumber::from(1_usize))])),
+            ])),
+        ]);
+
+        assert_eq!(spans[2].span, Span { start: 4, end: 15, line: 1, col: 4 });
+        assert_eq!(spans[2].inner[1].span, Span { start: 8, end: 13, line: 1, col: 7 });
+        assert_eq!(spans[2].inner[1].inner[0].span.col, 9);
+
+        let nums = |s| Pesc::parse(s).unwrap().1.into_iter()
+            .map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(nums("0xff _0b1_01 (0o1_7) (_0x_10) 1_000 (1_000.5) 2e3 (1e_2) _1e_2"),
+            vec!["255", "-5", "15", "-16", "1000", "1000.5", "2000", "0.01", "-0.01"]);
+
+        let s = PescToken::Str("\"tab\"\tand\\\n".into());
+        assert_eq!(Pesc::parse(&s.to_string()).unwrap().1, vec![s]);
+
+        let e = Pesc::parse("(0x_)").unwrap_err();
+        assert_eq!(e.kind, PescErrorType::InvalidNumberLit(String::from("0x_")));
+
+        let e = Pesc::parse("{ 1 }}").unwrap_err();
+        assert_eq!(e.kind, PescErrorType::UnmatchedToken('}'));
+        let e = Pesc::parse("1\n{ 1").unwrap_err();
+        assert_eq!(e.kind, PescErrorType::UnmatchedToken('{'));
+        assert_eq!(e.span.map(|s| (s.line, s.col)), Some((2, 1)));
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        // tail-recursive, runs in constant space
+        let p = run("{ \\ 0 [eq?] { 1 - [down] } , {} , ? } \"down\" [def] 100000 [down]")
+            .unwrap();
+        assert_eq!(p.stack, vec![PescToken::Number(PescNumber::from(0_usize))]);
+
+        // not tail-recursive, but the call stack is on the heap
+        let p = run("{ \\ 0 [eq?] { \\ 1 - [sum] + } , {} , ? } \"sum\" [def] 50000 [sum]")
+            .unwrap();
+        assert_eq!(p.stack, vec![PescToken::Number(PescNumber::from(1250025000_usize))]);
+    }
+
+    #[test]
+    fn test_rollback() {
+        let e = run("1 2 { 3 [foo] } ;").err().unwrap();
+        assert_eq!(e.kind, PescErrorType::UnknownFunction(String::from("foo")));
+
+        let mut p = run("").unwrap();
+        let code = Pesc::parse("1 2 { 3 [foo] } ;").unwrap().1;
+        let (badstack, _) = p.eval(&code).unwrap_err();
+        assert_eq!(badstack.len(), 3);
+        assert_eq!(p.stack.len(), 3);
+
+        // values popped or overwritten by the call are put back
+        let num = |n: usize| PescToken::Number(PescNumber::from(n));
+        p.stack.clear();
+
+        let code = Pesc::parse("1 2 3 4 { 2 @ $ $ 7 [foo] } ;").unwrap().1;
+        let (badstack, _) = p.eval(&code).unwrap_err();
+        assert_eq!(badstack, vec![num(1), num(4), num(7)]);
+        assert_eq!(p.stack[..4], [num(1), num(2), num(3), num(4)]);
+        assert_eq!(p.stack.len(), 5);
+
+        // and what's put back is the same value, not a copy of it
+        p.stack.clear();
+        p.eval(&Pesc::parse("'( '( 1 ) 2 )").unwrap().1).unwrap();
+        let list = p.stack[0].clone();
+        p.eval(&Pesc::parse("{ [reverse] [foo] } ;").unwrap().1).unwrap_err();
+        match (&list, &p.stack[..]) {
+            (PescToken::List(a), [PescToken::List(b), _]) => assert!(Rc::ptr_eq(a, b)),
+            _ => panic!("the list wasn't put back"),
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        let mut p = run("").unwrap();
+        let chunk = p.compile(Pesc::parse("1 + [Foo] Q").unwrap().1);
+
+        let add = p.funcs["add"];
+        let foo = p.funcs["foo"];
+        assert_eq!(chunk.ops, vec![Op::Push, Op::Call(add), Op::Call(foo), Op::Unknown]);
+        assert!(p.func("foo").is_none());
+
+        // functions may be called before they're defined, and
+        // redefined later on
+        let mut eval = |s: &str| p.eval(&Pesc::parse(s).unwrap().1).map_err(|(_, e)| e.kind);
+        assert_eq!(eval("{ [bar] } \"foo\" [def] [foo]"),
+            Err(PescErrorType::UnknownFunction(String::from("bar"))));
+        assert_eq!(eval("{ 1 } \"bar\" [def] [foo] { 2 } \"bar\" [def] [foo]"), Ok(()));
+        assert_eq!(p.stack, vec![PescToken::Number(PescNumber::from(1_usize)),
+            PescToken::Number(PescNumber::from(2_usize))]);
+
+        // a macro (and its copies) is only compiled once, until an
+        // operator is loaded that it might use
+        let m = Pesc::parse("{ 1 Q }").unwrap().1.remove(0);
+        let first = p.compile_callable(m.clone()).unwrap();
+        assert!(Rc::ptr_eq(&first, &p.compile_callable(m.clone()).unwrap()));
+        assert_eq!(first.ops[1], Op::Unknown);
+
+        let info = FuncInfo::new(crate::info::Category::Misc, "--", "");
+        p.load(Some('Q'), "q", Rc::new(Box::new(|_| Ok(()))), info);
+        let second = p.compile_callable(m.clone()).unwrap();
+        assert_eq!(second.ops[1], Op::Call(p.funcs["q"]));
+        assert!(Rc::ptr_eq(&second, &p.compile_callable(m).unwrap()));
+
+        // another interpreter compiles it afresh
+        let mut other = run("").unwrap();
+        let m = Pesc::parse("{ 1 Q }").unwrap().1.remove(0);
+        let ours = p.compile_callable(m.clone()).unwrap();
+        assert!(!Rc::ptr_eq(&ours, &other.compile_callable(m).unwrap()));
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut p = run("").unwrap();
+        p.limits = PescLimits { steps: Some(1000), depth: Some(50), stack: Some(20) };
+
+        let mut eval = |s: &str| {
+            let code = Pesc::parse(s).unwrap().1;
+            p.eval(&code).map_err(|(_, e)| e.kind)
+        };
+
+        let limit = |l: &str, n| Err(PescErrorType::LimitExceeded(String::from(l), n));
+
+        assert_eq!(eval("{ \\ ; } \\ ;"), limit("step", 1000));
+        assert_eq!(eval("{ [a] 1 + } \"a\" [def] [a]"), limit("call depth", 50));
+        assert_eq!(eval("{ 1 [b] } \"b\" [def] [b]"), limit("stack size", 20));
+        assert_eq!(eval("3 9 [ack]"), limit("step", 1000));
+
+        // each failing call was rolled back
+        assert_eq!(eval("$ $ $ $ 2 3 [ack]"), Ok(()));
+        assert_eq!(p.stack, vec![PescToken::Number(PescNumber::from(9_usize))]);
+
+        // loops that push values or run empty bodies still count
+        let mut p = run("").unwrap();
+        p.limits = PescLimits { steps: Some(1000), depth: None, stack: Some(20) };
+        let mut eval = |s: &str| {
+            let code = Pesc::parse(s).unwrap().1;
+            p.eval(&code).map_err(|(_, e)| e.kind)
+        };
+
+        assert_eq!(eval("1000000000000 { } [times]"), limit("step", 1000));
+        assert_eq!(eval("10 { 0 } { + } [primrec]"), Ok(()));
+        assert_eq!(eval("1000000000000 { 0 } { + } [primrec]"), limit("stack size", 20));
+
+        // huge exact results are approximated rather than computed
+        assert_eq!(eval("2 20000000 ^ 1 100000000000 <"), Ok(()));
+        let inf = PescToken::Number(PescNumber::Float(f64::INFINITY));
+        assert_eq!(p.stack[p.stack.len() - 2..], [inf.clone(), inf.clone()]);
+
+        let mut p = run("").unwrap();
+        p.limits = PescLimits { steps: Some(1000), depth: None, stack: None };
+        let mut eval = |s: &str| {
+            let code = Pesc::parse(s).unwrap().1;
+            p.eval(&code).map_err(|(_, e)| e.kind)
+        };
+
+        assert_eq!(eval("100000 [fact]"), limit("step", 1000));
+        assert_eq!(eval("999999999989 [prime]"), limit("step", 1000));
+        assert_eq!(eval("20 [fact] 1000003 [prime]"), Ok(()));
+
+        let p = run("100000 [fact]").unwrap();
+        assert_eq!(p.stack, vec![inf]);
+    }
+
+    #[test]
+    fn test_eval_atomic() {
+        let mut p = run("1 \"r\" [rpush] { 1 } \"f\" [def]").unwrap();
+        let num = |n: usize| PescToken::Number(PescNumber::from(n));
+
+        let (spans, code) = Pesc::parse("2 3 + \"r\" [rpop] \"r\" [rpop] { 2 } \"f\" [def] [foo]")
+            .unwrap();
+        let (badstack, e) = p.eval_atomic(&code, &spans).unwrap_err();
+        assert_eq!(e.kind, PescErrorType::EmptyRegister(String::from("r")));
+        assert_eq!(badstack, vec![num(5), num(1)]);
+
+        // everything is put back the way it was
+        assert!(p.stack.is_empty());
+        assert_eq!(p.registers["r"], vec![num(1)]);
+        assert_eq!(p.eval(&Pesc::parse("[f]").unwrap().1).map_err(|_| ()), Ok(()));
+        assert_eq!(p.stack, vec![num(1)]);
+
+        // nested calls can still be rolled back on their own
+        let code = Pesc::parse("2 { 3 [foo] } [try] $ { $ 4 } ;").unwrap().1;
+        assert_eq!(p.eval_atomic(&code, &[]).map_err(|_| ()), Ok(()));
+        assert_eq!(p.stack, vec![num(1), num(4)]);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut p = run("").unwrap();
+        p.history_limit = 2;
+
+        let eval = |p: &mut Pesc, s: &str| {
+            let (spans, code) = Pesc::parse(s).unwrap();
+            p.eval_atomic(&code, &spans).map_err(|(_, e)| e.kind)
+        };
+        let num = |n: usize| PescToken::Number(PescNumber::from(n));
+
+        eval(&mut p, "1").unwrap();
+        eval(&mut p, "{ 5 } \"f\" [def] 2").unwrap();
+        eval(&mut p, "$").unwrap();
+        eval(&mut p, "[foo]").unwrap_err();
+        assert_eq!(p.stack, vec![num(1)]);
+
+        // failed inputs aren't recorded
+        eval(&mut p, "[undo]").unwrap();
+        assert_eq!(p.stack, vec![num(1), num(2)]);
+
+        // only two inputs are kept
+        assert_eq!(eval(&mut p, "[undo] [undo]"), Err(PescErrorType::Other(String::from("There's nothing to undo."))));
+        assert_eq!(p.stack, vec![num(1), num(2)]);
+
+        eval(&mut p, "3 [undo]").unwrap();
+        assert_eq!(p.stack, vec![num(1)]);
+        assert_eq!(eval(&mut p, "[f]"), Err(PescErrorType::UnknownFunction(String::from("f"))));
+
+        assert!(p.redo());
+        assert_eq!(p.stack, vec![num(1), num(2)]);
+        eval(&mut p, "[redo] [f]").unwrap();
+        assert_eq!(p.stack, vec![num(1)]);
+        assert!(!p.redo());
+
+        eval(&mut p, "[f]").unwrap();
+        assert_eq!(p.stack, vec![num(1), num(5)]);
+    }
+
+    #[test]
+    fn test_combinators() {
+        let stack = |input: &str| run(input).unwrap().stack
+            .iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+
+        assert_eq!(stack("1 3 { 2 * } [times]"), "8");
+        assert_eq!(stack("1 { \\ 243 [eq?] ! } { 3 * } [while]"), "243");
+        assert_eq!(stack("1 2 3 { + } [dip]"), "3 3");
+        assert_eq!(stack("5 { \\ 0 [eq?] } { 1 + } { \\ 1 - } { * } [linrec]"), "120");
+        assert_eq!(stack("10 { \\ \\ 0 [eq?] , 1 [eq?] [or] } {} { \\ 1 - , 2 - } { + } [binrec]"), "55");
+        assert_eq!(stack("5 { 1 } { * } [primrec]"), "120");
+        assert_eq!(stack("0 { 1 2 3 } { + } [step]"), "6");
+        assert_eq!(stack("{ 1 2 3 } 0 { + } [fold]"), "6");
+        assert_eq!(stack("10 { 1 2 3 } { + } [map] [run]"), "10 11 12 13");
+        assert_eq!(stack("{ 1 2 3 4 } { [odd] } [filter] [run]"), "1 3");
+        assert_eq!(stack("\"abc\" { $ \"x\" } [map]"), "\"xxx\"");
+
+        // the loop stops at the first failure
+        assert!(run("0 5 { 1 + \\ 3 [eq?] { [foo] } { } ? } [times]").is_err());
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let stack = |input: &str| run(input).unwrap().stack
+            .iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+
+        // the value on top of the stack is compared against the one below
+        assert_eq!(stack("1 2 [gt?] 2 1 [gt?] 1 1 [gt?]"), "(true) (false) (false)");
+        assert_eq!(stack("2 1 [lt?] 1 2 [lt?] 1 1 [lt?]"), "(true) (false) (false)");
+        assert_eq!(stack("1 2 [gte] 2 1 [gte] 1 1 [gte]"), "(true) (false) (true)");
+        assert_eq!(stack("2 1 [lte] 1 2 [lte] 1 1 [lte]"), "(true) (false) (true)");
+    }
+
+    #[test]
+    fn test_lists() {
+        let stack = |input: &str| run(input).unwrap().stack
+            .iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+
+        assert_eq!(stack("'( 1 '( \"a\" ) (2+i) )"), "'(1 '(\"a\") 2+1i)");
+        assert_eq!(stack("1 2 3 3 [list]"), "'(1 2 3)");
+        assert_eq!(stack("'( 1 2 3 ) 1 [nth]"), "2");
+        assert_eq!(stack("'( 1 2 3 4 ) 1 3 [slice]"), "'(2 3)");
+        assert_eq!(stack("'( 1 2 3 ) [len]"), "3");
+        assert_eq!(stack("'( 1 ) '( 2 3 ) [concat] [reverse]"), "'(3 2 1)");
+        assert_eq!(stack("'( 1 2 ) [explode]"), "1 2");
+        assert_eq!(stack("'( 1 2 3 ) { 2 * } [map]"), "'(2 4 6)");
+
+        assert_eq!(run("'( 1 ) 1 [nth]").err().unwrap().kind,
+            PescErrorType::IndexOutOfBounds(String::from("list"), PescNumber::from(1_usize), 1));
+        assert_eq!(Pesc::parse("'( 1 }").unwrap_err().kind,
+            PescErrorType::UnmatchedToken('}'));
+        assert_eq!(Pesc::parse("{ '( 1 }").unwrap_err().kind,
+            PescErrorType::UnmatchedToken('}'));
+        assert_eq!(Pesc::parse("'( 1 ").unwrap_err().kind,
+            PescErrorType::UnmatchedToken('('));
+    }
+
+    #[test]
+    fn test_maps() {
+        let stack = |input: &str| run(input).unwrap().stack
+            .iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+
+        assert_eq!(stack("'{ \"b\" '{ } \"a\" 1 }"), "'{\"a\" 1 \"b\" '{}}");
+        assert_eq!(stack("'{ \"a\" 1 } \"a\" [at]"), "1");
+        assert_eq!(stack("'{ } \"a\" 1 [put] \"b\" 2 [put] \\ [keys] , [values]"),
+            "'(\"a\" \"b\") '(1 2)");
+        assert_eq!(stack("'{ \"a\" 1 } \\ \"a\" [has] , \"a\" [del] \"a\" [has]"),
+            "(true) (false)");
+        assert_eq!(stack("'{ \"a\" 1 \"b\" 2 } '{ \"b\" 2 \"a\" 1 } [eq?]"), "(true)");
+
+        assert_eq!(run("'{ } \"a\" [at]").err().unwrap().kind,
+            PescErrorType::NoSuchKey(String::from("a")));
+        assert_eq!(Pesc::parse("'{ \"a\" }").unwrap_err().kind,
+            PescErrorType::MissingValue(String::from("a")));
+        assert_eq!(Pesc::parse("'{ 1 2 )").unwrap_err().kind,
+            PescErrorType::UnmatchedToken(')'));
+    }
+
+    #[test]
+    fn test_strings() {
+        let stack = |input: &str| run(input).unwrap().stack
+            .iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+
+        assert_eq!(stack("\"héllo\" \\ [len] , \" wörld\" [concat]"), "5 \"héllo wörld\"");
+        assert_eq!(stack("'( 1 ) '( 2 ) [concat] [len]"), "2");
+        assert_eq!(stack("\"héllo\" 1 3 [substr] [upper]"), "\"ÉL\"");
+        assert_eq!(stack("\"a,b,,c\" \",\" [split]"), "'(\"a\" \"b\" \"\" \"c\")");
+        assert_eq!(stack("\"ab\" \"\" [split] \"-\" [join]"), "\"a-b\"");
+        assert_eq!(stack("\" Ab \" [trim] [lower] \"ab\" [eq?]"), "(true)");
+        assert_eq!(stack("\"héllo\" \"l\" [find] \"x\" \"y\" [find]"), "2 -1");
+        assert_eq!(stack("\"a.b.c\" \".\" \"::\" [replace] \"ab\" 3 [repeat]"),
+            "\"a::b::c\" \"ababab\"");
+
+        assert_eq!(stack("1 3 / [str] \" _0x_ff \" [num] \"1e_2\" [num]"), "\"1/3\" -255 0.01");
+        assert_eq!(stack("2 3 / \".3\" [fmt] 2 \".2\" [fmt] 1 3 / \"\" [fmt]"),
+            "\"0.667\" \"2.00\" \"1/3\"");
+        assert_eq!(stack("255 \"x\" [fmt] _5 \"b\" [fmt] 1234.5 \".1e\" [fmt]"),
+            "\"0xff\" \"-0b101\" \"1.2e3\"");
+        assert_eq!(stack("255 \"x\" [fmt] [num]"), "255");
+
+        // absurd precisions are clamped rather than allocated
+        let p = run("0.5 [sqrt] \".99999999999\" [fmt] 1 3 / \".99999999999\" [fmt] \
+            2 \".99999999999e\" [fmt]").unwrap();
+        assert_eq!(p.stack.iter().map(|t| t.to_string().len()).collect::<Vec<_>>(),
+            vec![1074 + 4, 16384 + 4, 1074 + 6]);
+
+        assert_eq!(run("\"1.2.3\" [num]").err().unwrap().kind,
+            PescErrorType::InvalidNumberLit(String::from("1.2.3")));
+        assert_eq!(run("1 \".x\" [fmt]").err().unwrap().kind,
+            PescErrorType::InvalidArgumentType(String::from("format"), String::from("\".x\"")));
+        assert_eq!(run("\"a\" '( 1 ) [concat]").err().unwrap().kind,
+            PescErrorType::InvalidArgumentType(String::from("string"), String::from("'(1)")));
+        assert_eq!(run("\"ab\" 0 5 [substr]").err().unwrap().kind.to_string(),
+            "There's nothing at 5 in a string of length 2.");
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        let stack = |input: &str| run(input).unwrap().stack
+            .iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+
+        assert_eq!(stack("\"1 { 2 } [add]\" [parse]"), "{ 1 { 2 } [add] }");
+        assert_eq!(stack("\"2 3 ×\" [unquote] 1 +"), "7");
+        assert_eq!(stack("{ \"a\\n\" '( 1 _2.5 ) } \\ [quote] [parse] [eq?]"), "(true)");
+        assert_eq!(stack("{ 1 2 + } [quote]"), "\"1 2 +\"");
+        assert_eq!(stack("\"{ \\\\ × } \" `\"sq\"` [concat] [unquote] [def] 3 [sq]"), "9");
+
+        assert_eq!(run("\"1 }\" [parse]").err().unwrap().kind,
+            PescErrorType::UnmatchedToken('}'));
+        assert_eq!(run("\"[nope]\" [unquote]").err().unwrap().kind,
+            PescErrorType::UnknownFunction(String::from("nope")));
+    }
+
+    #[test]
+    fn test_macros() {
+        let stack = |input: &str| run(input).unwrap().stack
+            .iter().map(pretty::token).collect::<Vec<_>>().join(" ");
+
+        assert_eq!(stack("1 { 2 + } [cons]"), "{ 1 2 + }");
+        assert_eq!(stack("{ + 2 } [uncons]"), "+ { 2 }");
+        assert_eq!(stack("{ [add] 2 } [first] { [add] 2 } [rest]"), "[add] { 2 }");
+        assert_eq!(stack("{ 1 } { [add] } [concat] \\ [len] , ;"), "3");
+        assert_eq!(stack("'( 1 ) [first] \"ab\" [uncons]"), "1 \"a\" \"b\"");
+        assert_eq!(stack("\"a\" \"bc\" [cons] '( 2 ) 1 , [cons]"), "\"abc\" '( 1 2 )");
+
+        // taking code apart and putting it back together
+        assert_eq!(stack("3 { 2 × } [uncons] [uncons] [pop] [quote1] [cons] { 1 + } [concat] ;"),
+            "7");
+        assert_eq!(stack("{ 1 } [quote1] \\ [len] , ;"), "1 { 1 }");
+        assert_eq!(stack("2 3 { × } [first] [quote1] ;"), "6");
+
+        assert_eq!(run("{ } [first]").err().unwrap().kind,
+            PescErrorType::EmptySequence(String::from("macro")));
+        assert_eq!(run("\"\" [uncons]").err().unwrap().kind.to_string(),
+            "There's nothing in that string; it's empty.");
+        assert_eq!(run("{ 1 } '( 2 ) [concat]").err().unwrap().kind,
+            PescErrorType::InvalidArgumentType(String::from("macro"), String::from("'(2)")));
+        assert_eq!(run("1 \"bc\" [cons]").err().unwrap().kind,
+            PescErrorType::InvalidArgumentType(String::from("string"), String::from("1")));
+    }
+
+    #[test]
+    fn test_nesting() {
+        let limit = PescErrorType::LimitExceeded(String::from("nesting"), MAX_NESTING);
+
+        // as deep as values can go, which can still be printed, saved,
+        // compared, and dropped
+        let deep = format!("{{ }} {} {{ [quote1] }} [times]", MAX_NESTING - 1);
+        let mut p = run(&deep).unwrap();
+        assert_eq!(p.stack[0].nesting(), MAX_NESTING);
+        assert_eq!(p.stack[0].to_string().matches('{').count(), MAX_NESTING);
+        assert_eq!(Session::read(&p.save()).unwrap().stack, p.stack);
+        p.eval(&Pesc::parse("\\ [quote] $ \\ [eq?] $").unwrap().1).unwrap();
+        assert!(p.stack.is_empty());
+
+        // but no deeper, however they're built
+        let deeper = |code: &str| run(&format!("{} {}", deep, code)).err().unwrap().kind;
+        assert_eq!(deeper("[quote1]"), limit);
+        assert_eq!(deeper("1 [list]"), limit);
+        assert_eq!(deeper("{ } [cons]"), limit);
+        assert_eq!(deeper("'{ } , \"k\" , [put]"), limit);
+        assert_eq!(deeper("{ [quote1] } [map]"), limit);
+
+        let src = format!("{}{}", "'( ".repeat(MAX_NESTING + 1), ")".repeat(MAX_NESTING + 1));
+        assert_eq!(Pesc::parse(&src).unwrap_err().kind, limit);
+        assert!(Pesc::parse(&src[3..src.len() - 1]).is_ok());
+    }
+
+    #[test]
+    fn test_save() {
+        let p = run("1 { \\ × } \"sq\" [def] { [sq] 1 + } \"f\" [def] 3 \"a\" [rpush]").unwrap();
+        let saved = p.save();
+
+        let mut q = run("5").unwrap();
+        q.restore(&saved).unwrap();
+        assert_eq!(q.save(), saved);
+
+        let parsed = Pesc::parse("[f] \"a\" [load] [sq]").unwrap().1;
+        q.eval(&parsed).unwrap();
+        assert_eq!(q.stack, vec![
+            PescToken::Number(PescNumber::from(2_usize)),
+            PescToken::Number(PescNumber::from(9_usize)),
+        ]);
+
+        assert!(q.restore("pesc-session 1\nstack\nmacro 1\n").is_err());
+        assert_eq!(q.stack.len(), 2);
+    }
+
+    #[test]
+    fn test_registers() {
+        let p = run("1 \"a\" [rpush] 2 \"a\" [rpush] 3 \"a\" [store] \"a\" [load] \"a\" [rpop] \"a\" [rpop]")
+            .unwrap();
+        assert_eq!(p.stack, vec![PescToken::Number(PescNumber::from(3_usize)),
+            PescToken::Number(PescNumber::from(3_usize)),
+            PescToken::Number(PescNumber::from(1_usize))]);
+        assert!(p.registers["a"].is_empty());
+
+        let e = run("\"b\" [load]").err().unwrap();
+        assert_eq!(e.kind, PescErrorType::EmptyRegister(String::from("b")));
+    }
+
+    #[test]
+    fn test_underflow() {
+        let e = run("1 +").err().unwrap();
+        assert_eq!(e.kind, PescErrorType::NotEnoughArguments(
+            Some((String::from("add"), 2, 1))));
+        assert_eq!(e.hints(), vec!["add needs 2 arguments, got 1."]);
+
+        // the innermost function to run out is the one reported
+        let e = run("1 { $ $ } [dip]").err().unwrap();
+        assert_eq!(e.hints(), vec!["pop needs 1 argument, got 0."]);
+
+        let e = run("{ } T [if?]").err().unwrap();
+        assert_eq!(e.hints(), vec!["if? needs 3 arguments, got 2."]);
+
+        assert_eq!(run("").unwrap().info("+").map(|i| i.arity()), None);
+        assert_eq!(run("").unwrap().info("add").unwrap().to_string(),
+            "a:number b:number -- number");
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(printed("\"×\" [help]"), vec![
+            "mul (* ×): a:number b:number -- number", "    a * b"]);
+        assert_eq!(printed("{ \\ × } \"sq\" [def] \"sq\" [see] \"+\" [see]"), vec![
+            "{ \\ × } \"sq\" [def]",
+            "add is built in; see what it does with \"add\" [help]"]);
+
+        let words = printed("{ } \"nop\" [def] [words]");
+        assert!(words[0].starts_with("arithmetic: add (+), div (/ ÷)"));
+        assert_eq!(words.last().unwrap(), "defined: nop");
+
+        assert_eq!(printed("{ 1 '( _2 ) [f] } [dbg] \"a\" [dbg] $"), vec![
+            "{ 1 '( _2 ) [f] }", "\"a\""]);
+
+        let e = run("\"nope\" [help]").err().unwrap();
+        assert_eq!(e.kind, PescErrorType::UnknownFunction(String::from("nope")));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::number::PescNumber;
+use crate::pesc::{PescToken, BOOLEAN_TRUE, BOOLEAN_FALSE};
+
+/// Write some code back out as Pesc source, which gives the same
+/// tokens when it's parsed again.
+///
+/// # Examples
+///
+/// ```
+/// use pesc::pesc::Pesc;
+/// use pesc::pretty;
+///
+/// let code = Pesc::parse("{ 1 _2 [add] } \"sq\"").unwrap().1;
+/// assert_eq!(pretty::source(&code), "{ 1 _2 [add] } \"sq\"");
+/// ```
+pub fn source(code: &[PescToken]) -> String {
+    code.iter().map(token).collect::<Vec<_>>().join(" ")
+}
+
+/// Write a single token out as Pesc source.
+pub fn token(t: &PescToken) -> String {
+    match t {
+        PescToken::Macro(m) => delimited("{", m, "}"),
+        PescToken::List(l) => delimited("'(", l, ")"),
+        PescToken::Map(m) => {
+            let pairs = m.iter()
+                .flat_map(|(k, v)| vec![PescToken::Str(k.as_str().into()), v.clone()])
+                .collect::<Vec<_>>();
+            delimited("'{", &pairs, "}")
+        },
+        PescToken::Number(n) => number(n),
+        PescToken::Str(s) => string(s),
+        PescToken::Func(f) => format!("[{}]", f),
+        PescToken::Symbol(c) => c.to_string(),
+        PescToken::Bool(true) => BOOLEAN_TRUE.to_string(),
+        PescToken::Bool(false) => BOOLEAN_FALSE.to_string(),
+    }
+}
+
+/// Write a string out as a Pesc string literal, escaping anything that
+/// wouldn't read back the same.
+///
+/// # Examples
+///
+/// ```
+/// use pesc::pretty;
+///
+/// assert_eq!(pretty::string("say \"hi\"\n"), r#""say \"hi\"\n""#);
+/// ```
+pub fn string(s: &str) -> String {
+    let mut buf = String::with_capacity(s.len() + 2);
+    buf.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\t' => buf.push_str("\\t"),
+            '\r' => buf.push_str("\\r"),
+            c if c.is_control() => buf.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => buf.push(c),
+        }
+    }
+
+    buf.push('"');
+    buf
+}
+
+fn delimited(open: &str, code: &[PescToken], close: &str) -> String {
+    match code.is_empty() {
+        true => format!("{}{}", open, close),
+        false => format!("{} {} {}", open, source(code), close),
+    }
+}
+
+// numbers are written bare where possible ("12", "_3.5", "1e_1"), and
+// in parentheses otherwise ("(1/3)", "(3+2i)", "(inf)"). finite floats
+// always get an exponent, since "0.1" and "2" would read back as a
+// ratio and an integer; Rust's LowerExp gives the shortest digits that
+// read back as the same f64.
+fn number(n: &PescNumber) -> String {
+    let s = match n {
+        PescNumber::Float(f) if f.is_finite() => format!("{:e}", f).replace("e-", "e_"),
+        PescNumber::Float(f) => format!("{:?}", f),
+        PescNumber::Complex(c) if c.re == 0.0 => format!("{:?}i", c.im),
+        PescNumber::Complex(c) if c.im.is_sign_negative() =>
+            format!("{:?}-{:?}i", c.re, -c.im),
+        PescNumber::Complex(c) => format!("{:?}+{:?}i", c.re, c.im),
+        _ => n.to_string(),
+    };
+    let bare = |s: &str| !s.is_empty()
+        && s.chars().all(|c| c.is_ascii_digit() || c == '.' || c == 'e' || c == '_');
+
+    match s.strip_prefix('-') {
+        Some(digits) if bare(digits) => format!("_{}", digits),
+        None if bare(&s) => s,
+        _ => format!("({})", s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pesc::Pesc;
+
+    #[test]
+    fn test_round_trip() {
+        let src = "1 _2 0.25 1e_1 _2.5e0 (1/3) (-2/3) (3.0+2.0i) (-1.5i) \"a b\" T F + ø [foo] \
+            \"\\\"q\\\" \\\\ \\n\\t\\r\\u{7f}é\" \
+            { } { 1 { [f] ; } } '() '( 1 '( 2 ) { } ) '{} '{ \"k\" '{ \"\" 1 } }";
+        let code = Pesc::parse(src).unwrap().1;
+
+        assert_eq!(source(&code), src.replace("{ }", "{}"));
+        // PescNumber's PartialEq compares values across variants, so
+        // compare the Debug output too to catch e.g. a Float read back
+        // as an Int.
+        let back = Pesc::parse(&source(&code)).unwrap().1;
+        assert_eq!(back, code);
+        assert_eq!(format!("{:?}", back), format!("{:?}", code));
+
+        let floats = vec![0.1, 2.0, -2.5, 0.0, 1e300, -1e-300, f64::INFINITY, f64::NEG_INFINITY];
+        for f in floats {
+            let t = PescToken::Number(PescNumber::Float(f));
+            match &Pesc::parse(&token(&t)).unwrap().1[..] {
+                [PescToken::Number(PescNumber::Float(g))] => assert_eq!(*g, f),
+                other => panic!("{} read back as {:?}", f, other),
+            }
+        }
+        assert_eq!(token(&PescToken::Number(PescNumber::Float(0.1))), "1e_1");
+        assert_eq!(token(&PescToken::Number(PescNumber::Float(-2.0))), "_2e0");
+        assert_eq!(token(&PescToken::Number(PescNumber::Float(1e300))), "1e300");
+
+        let raw = Pesc::parse(r#"`C:\dir\"x"`"#).unwrap().1;
+        assert_eq!(source(&raw), r#""C:\\dir\\\"x\"""#);
+        assert_eq!(Pesc::parse(&source(&raw)).unwrap().1, raw);
+    }
+}
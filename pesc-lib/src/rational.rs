@@ -0,0 +1,323 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use crate::bigint::BigInt;
+use crate::utils::gcd;
+
+/// An exact fraction of two arbitrary-precision integers.
+///
+/// Rationals are always kept in lowest terms with a positive
+/// denominator, so two equal rationals always have the same
+/// representation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl Rational {
+    /// Create a new rational, returning None if the denominator
+    /// is zero.
+    pub fn new(num: BigInt, den: BigInt) -> Option<Self> {
+        if den.is_zero() {
+            return None;
+        }
+
+        let g = gcd(&num, &den);
+        let (mut num, _) = num.divrem(&g).unwrap();
+        let (mut den, _) = den.divrem(&g).unwrap();
+
+        if den.is_negative() {
+            num = -num;
+            den = -den;
+        }
+
+        Some(Self { num, den })
+    }
+
+    /// Parse a decimal literal such as "12.5" or "-.25" exactly.
+    pub fn parse_decimal(s: &str) -> Option<Self> {
+        let (neg, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+
+        let mut parts = digits.splitn(2, '.');
+        let int = parts.next().unwrap_or("");
+        let frac = parts.next().unwrap_or("");
+
+        if int.is_empty() && frac.is_empty() {
+            return None;
+        }
+
+        // "12.5" => 125 / 10^1
+        let num = BigInt::parse(&format!("{}{}", int, frac))?;
+        let den = BigInt::from(10_u64).pow(frac.len() as u32);
+        let r = Self::new(num, den)?;
+
+        Some(if neg { -r } else { r })
+    }
+
+    pub fn numer(&self) -> &BigInt {
+        &self.num
+    }
+
+    pub fn denom(&self) -> &BigInt {
+        &self.den
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den == BigInt::one()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num.is_zero()
+    }
+
+    pub fn abs(&self) -> Self {
+        Self { num: self.num.abs(), den: self.den.clone() }
+    }
+
+    pub fn recip(&self) -> Option<Self> {
+        Self::new(self.den.clone(), self.num.clone())
+    }
+
+    /// Raise to an integer power; returns None for negative powers
+    /// of zero.
+    pub fn pow(&self, exp: i64) -> Option<Self> {
+        let e = exp.unsigned_abs() as u32;
+        let r = Self {
+            num: self.num.pow(e),
+            den: self.den.pow(e),
+        };
+
+        if exp < 0 { r.recip() } else { Some(r) }
+    }
+
+    /// Round towards negative infinity.
+    pub fn floor(&self) -> BigInt {
+        let (q, r) = self.num.divrem(&self.den).unwrap();
+        if r.is_negative() { &q - &BigInt::one() } else { q }
+    }
+
+    /// Round towards positive infinity.
+    pub fn ceil(&self) -> BigInt {
+        let (q, r) = self.num.divrem(&self.den).unwrap();
+        if !r.is_negative() && !r.is_zero() { &q + &BigInt::one() } else { q }
+    }
+
+    /// Round to the nearest integer, with halfway cases rounded away
+    /// from zero (like f64::round).
+    pub fn round(&self) -> BigInt {
+        let half = Self { num: BigInt::one(), den: BigInt::from(2_u64) };
+        if self.num.is_negative() {
+            -(&(-self) + &half).floor()
+        } else {
+            (self + &half).floor()
+        }
+    }
+
+    /// Round towards zero.
+    pub fn trunc(&self) -> BigInt {
+        self.num.divrem(&self.den).unwrap().0
+    }
+
+    /// Convert to the nearest float.
+    pub fn to_f64(&self) -> f64 {
+        // dividing the f64 approximations of huge numerators and
+        // denominators would give inf/inf, so scale the fraction
+        // to have ~64 significant bits first and divide exactly.
+        let shift = 64 - (self.num.bits() as i64 - self.den.bits() as i64);
+        let q = if shift >= 0 {
+            self.num.shl(shift as usize).divrem(&self.den).unwrap().0
+        } else {
+            self.num.divrem(&self.den.shl(-shift as usize)).unwrap().0
+        };
+
+        q.to_f64() * 2_f64.powf(-shift as f64)
+    }
+
+    /// The number of digits after the decimal point needed to write
+    /// the rational in full, if it doesn't repeat forever.
+    fn decimal_places(&self) -> Option<usize> {
+        // only fractions whose denominator has no prime factors
+        // other than 2 and 5 have a terminating expansion
+        let five = BigInt::from(5_u64);
+
+        let twos = self.den.trailing_zeros();
+        let mut den = self.den.shr(twos);
+        let mut fives = 0;
+
+        while let Some((q, r)) = den.divrem(&five) {
+            if !r.is_zero() {
+                break;
+            }
+
+            den = q;
+            fives += 1;
+        }
+
+        if den == BigInt::one() {
+            Some(twos.max(fives))
+        } else {
+            None
+        }
+    }
+}
+
+impl From<BigInt> for Rational {
+    fn from(i: BigInt) -> Self {
+        Self { num: i, den: BigInt::one() }
+    }
+}
+
+impl Display for Rational {
+    // terminating fractions are written out in decimal, everything
+    // else as "numerator/denominator". with a precision ("{:.3}"),
+    // everything is written in decimal, rounded to that many places.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let places = match (f.precision(), self.decimal_places()) {
+            (Some(0), _) => return write!(f, "{}", self.round()),
+            (Some(p), _) => p,
+            (None, Some(p)) if p > 0 => p,
+            (None, Some(_)) => return write!(f, "{}", self.num),
+            (None, None) => return write!(f, "{}/{}", self.num, self.den),
+        };
+
+        let scale = Rational::from(BigInt::from(10_u64).pow(places as u32));
+        let digits = (&self.abs() * &scale).round();
+        let digits = format!("{:0>1$}", digits.to_string(), places + 1);
+        let (int, frac) = digits.split_at(digits.len() - places);
+
+        write!(f, "{}{}.{}", if self.num.is_negative() { "-" } else { "" },
+            int, frac)
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // denominators are always positive, so cross-multiplying
+        // doesn't flip anything
+        (&self.num * &other.den).cmp(&(&other.num * &self.den))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        Rational { num: -&self.num, den: self.den.clone() }
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        -&self
+    }
+}
+
+impl<'a> Add<&'a Rational> for &'a Rational {
+    type Output = Rational;
+
+    fn add(self, other: &Rational) -> Rational {
+        let num = &(&self.num * &other.den) + &(&other.num * &self.den);
+        Rational::new(num, &self.den * &other.den).unwrap()
+    }
+}
+
+impl<'a> Sub<&'a Rational> for &'a Rational {
+    type Output = Rational;
+
+    fn sub(self, other: &Rational) -> Rational {
+        self + &(-other)
+    }
+}
+
+impl<'a> Mul<&'a Rational> for &'a Rational {
+    type Output = Rational;
+
+    fn mul(self, other: &Rational) -> Rational {
+        Rational::new(&self.num * &other.num, &self.den * &other.den).unwrap()
+    }
+}
+
+impl<'a> Div<&'a Rational> for &'a Rational {
+    type Output = Option<Rational>;
+
+    // returns None when dividing by zero
+    fn div(self, other: &Rational) -> Option<Rational> {
+        Rational::new(&self.num * &other.den, &self.den * &other.num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rat(n: i64, d: i64) -> Rational {
+        Rational::new(BigInt::from(n), BigInt::from(d)).unwrap()
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(rat(2, 4), rat(1, 2));
+        assert_eq!(rat(1, -3), rat(-1, 3));
+        assert_eq!(rat(-1, 3).denom(), &BigInt::from(3_i64));
+        assert!(Rational::new(BigInt::one(), BigInt::zero()).is_none());
+    }
+
+    #[test]
+    fn test_arith() {
+        assert_eq!(&rat(1, 10) + &rat(2, 10), rat(3, 10));
+        assert_eq!(&rat(1, 3) - &rat(1, 2), rat(-1, 6));
+        assert_eq!(&rat(2, 3) * &rat(3, 4), rat(1, 2));
+        assert_eq!((&rat(1, 3) / &rat(2, 1)).unwrap(), rat(1, 6));
+        assert_eq!(rat(2, 3).pow(-2).unwrap(), rat(9, 4));
+        assert!(rat(1, 3) > rat(1, 4));
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(rat(7, 2).floor(), BigInt::from(3_i64));
+        assert_eq!(rat(-7, 2).floor(), BigInt::from(-4_i64));
+        assert_eq!(rat(7, 2).ceil(), BigInt::from(4_i64));
+        assert_eq!(rat(-7, 2).ceil(), BigInt::from(-3_i64));
+        assert_eq!(rat(5, 2).round(), BigInt::from(3_i64));
+        assert_eq!(rat(-5, 2).round(), BigInt::from(-3_i64));
+        assert_eq!(rat(-7, 2).trunc(), BigInt::from(-3_i64));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(rat(1, 3).to_string(), "1/3");
+        assert_eq!(rat(-2, 3).to_string(), "-2/3");
+        assert_eq!(rat(3, 10).to_string(), "0.3");
+        assert_eq!(rat(-1, 40).to_string(), "-0.025");
+        assert_eq!(rat(5, 2).to_string(), "2.5");
+        assert_eq!(Rational::parse_decimal("3.14_15".replace("_", "").as_str())
+            .unwrap().to_string(), "3.1415");
+
+        assert_eq!(format!("{:.3} {:.2} {:.0}", rat(-2, 3), rat(1, 4), rat(5, 2)),
+            "-0.667 0.25 3");
+        assert_eq!(format!("{:.2}", rat(1, 200)), "0.01");
+    }
+
+    #[test]
+    fn test_to_f64() {
+        assert_eq!(rat(1, 4).to_f64(), 0.25);
+        assert_eq!(rat(-1, 3).to_f64(), -1.0 / 3.0);
+
+        let huge = Rational::new(BigInt::from(10_u64).pow(400),
+            BigInt::from(10_u64).pow(399) * BigInt::from(3_u64)).unwrap();
+        assert_eq!(huge.to_f64(), 10.0 / 3.0);
+    }
+}
@@ -0,0 +1,410 @@
+use std::mem;
+use std::collections::{BTreeMap, HashMap};
+use crate::bigint::BigInt;
+use crate::complex::Complex;
+use crate::errors::*;
+use crate::lexer::Span;
+use crate::number::PescNumber;
+use crate::pesc::{PescToken, MAX_NESTING};
+use crate::rational::Rational;
+
+// the first line of every session, bumped whenever the format changes
+const HEADER: &str = "pesc-session 1";
+
+/// The state of a Pesc interpreter that can be saved to a file: the
+/// stack, functions defined with [def], and registers.
+///
+/// Sessions are written as text, one token per line, with macros
+/// followed by the tokens inside them:
+///
+/// ```text
+/// pesc-session 1
+/// stack
+/// macro 2
+///   int 12
+///   str 5 hello
+/// def 2 sq
+/// macro 2
+///   sym \
+///   sym *
+/// ```
+///
+/// Strings and names are prefixed with their length in bytes, so they
+/// never need escaping, and floats are written as their raw bits so
+/// that they come back exactly the same. Symbols that are whitespace
+/// are written as their code point in hex ("sym u{d}").
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub stack: Vec<PescToken>,
+    pub defs: Vec<(String, Vec<PescToken>)>,
+    pub registers: HashMap<String, Vec<PescToken>>,
+}
+
+impl Session {
+    pub fn write(&self) -> String {
+        let mut buf = format!("{}\n", HEADER);
+
+        buf += "stack\n";
+        write_seq(&mut buf, "macro", &self.stack, 0);
+
+        for (name, body) in &self.defs {
+            buf += &format!("def {} {}\n", name.len(), name);
+            write_seq(&mut buf, "macro", body, 0);
+        }
+
+        // sort the registers so that saving the same state twice gives
+        // the same file
+        let mut registers = self.registers.iter().collect::<Vec<_>>();
+        registers.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (name, values) in registers {
+            buf += &format!("reg {} {}\n", name.len(), name);
+            write_seq(&mut buf, "macro", values, 0);
+        }
+
+        buf
+    }
+
+    pub fn read(src: &str) -> Result<Self, PescError> {
+        let mut r = Reader { src, pos: 0 };
+        let mut session = Session::default();
+
+        if !src.starts_with(HEADER) {
+            return Err(r.error(0, 0, "a \"pesc-session\" header"));
+        }
+        r.pos = HEADER.len();
+
+        while let Some((start, word)) = r.word() {
+            match word {
+                "stack" => session.stack = r.body()?,
+                "def" => {
+                    let name = r.bytes()?;
+                    let body = r.body()?;
+                    session.defs.push((name, body));
+                },
+                "reg" => {
+                    let name = r.bytes()?;
+                    let values = r.body()?;
+                    session.registers.insert(name, values);
+                },
+                _ => return Err(r.error(start, r.pos, "\"stack\", \"def\", or \"reg\"")),
+            }
+        }
+
+        Ok(session)
+    }
+}
+
+// the things write_seq has left to write: tokens, and the keys of maps
+// (which are written as strings).
+enum Item<'a> {
+    Token(&'a PescToken),
+    Key(&'a str),
+}
+
+// write a macro or list, and everything inside it. this is a loop
+// rather than being recursive so that deeply nested values can't
+// overflow Rust's stack.
+fn write_seq(buf: &mut String, kind: &str, code: &[PescToken], depth: usize) {
+    *buf += &format!("{}{} {}\n", "  ".repeat(depth), kind, code.len());
+
+    let mut todo = code.iter().rev()
+        .map(|t| (Item::Token(t), depth + 1))
+        .collect::<Vec<_>>();
+
+    while let Some((item, depth)) = todo.pop() {
+        let t = match item {
+            Item::Token(t) => t,
+            Item::Key(k) => {
+                *buf += &format!("{}str {} {}\n", "  ".repeat(depth), k.len(), k);
+                continue;
+            },
+        };
+
+        let line = match t {
+            PescToken::Macro(m) => {
+                todo.extend(m.iter().rev().map(|t| (Item::Token(t), depth + 1)));
+                format!("macro {}", m.len())
+            },
+            PescToken::List(l) => {
+                todo.extend(l.iter().rev().map(|t| (Item::Token(t), depth + 1)));
+                format!("list {}", l.len())
+            },
+            PescToken::Map(m) => {
+                // each key is written as a string, followed by its value
+                for (k, v) in m.iter().rev() {
+                    todo.push((Item::Token(v), depth + 1));
+                    todo.push((Item::Key(k), depth + 1));
+                }
+                format!("map {}", m.len())
+            },
+            PescToken::Number(PescNumber::Int(i)) => format!("int {}", i),
+            PescToken::Number(PescNumber::Ratio(r)) =>
+                format!("ratio {} {}", r.numer(), r.denom()),
+            PescToken::Number(PescNumber::Float(f)) =>
+                format!("float {:x}", f.to_bits()),
+            PescToken::Number(PescNumber::Complex(c)) =>
+                format!("complex {:x} {:x}", c.re.to_bits(), c.im.to_bits()),
+            PescToken::Str(s) => format!("str {} {}", s.len(), s),
+            PescToken::Func(f) => format!("fn {} {}", f.len(), f),
+            // a whitespace symbol would be skipped when it's read back, so
+            // it's written as its code point instead
+            PescToken::Symbol(c) if c.is_whitespace() => format!("sym u{{{:x}}}", *c as u32),
+            PescToken::Symbol(c) => format!("sym {}", c),
+            PescToken::Bool(b) => format!("bool {}", b),
+        };
+
+        *buf += &format!("{}{}\n", "  ".repeat(depth), line);
+    }
+}
+
+struct Reader<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    // skip whitespace and read a word, along with where it starts.
+    fn word(&mut self) -> Option<(usize, &'a str)> {
+        let rest = &self.src[self.pos..];
+        let start = self.pos + (rest.len() - rest.trim_start().len());
+        let len = self.src[start..]
+            .find(char::is_whitespace)
+            .unwrap_or(self.src.len() - start);
+
+        if len == 0 {
+            return None;
+        }
+
+        self.pos = start + len;
+        Some((start, &self.src[start..self.pos]))
+    }
+
+    fn expect_word(&mut self, expected: &str) -> Result<(usize, &'a str), PescError> {
+        match self.word() {
+            Some(w) => Ok(w),
+            None => Err(self.error(self.pos, self.pos, expected)),
+        }
+    }
+
+    fn hex(&mut self) -> Result<f64, PescError> {
+        let (start, word) = self.expect_word("a float")?;
+        match u64::from_str_radix(word, 16) {
+            Ok(bits) => Ok(f64::from_bits(bits)),
+            Err(_) => Err(self.error(start, self.pos, "a float")),
+        }
+    }
+
+    fn int(&mut self) -> Result<BigInt, PescError> {
+        let (start, word) = self.expect_word("an integer")?;
+        match BigInt::parse(word) {
+            Some(i) => Ok(i),
+            None => Err(self.error(start, self.pos, "an integer")),
+        }
+    }
+
+    fn length(&mut self) -> Result<usize, PescError> {
+        let (start, word) = self.expect_word("a length")?;
+        match word.parse::<usize>() {
+            Ok(l) => Ok(l),
+            Err(_) => Err(self.error(start, self.pos, "a length")),
+        }
+    }
+
+    // read a length-prefixed string.
+    fn bytes(&mut self) -> Result<String, PescError> {
+        let start = self.pos;
+        let len = self.length()?;
+
+        // the string starts after the single space following the length
+        let from = self.pos + 1;
+        let to = from.checked_add(len);
+        match to.and_then(|to| self.src.get(from..to)) {
+            Some(s) if self.src[self.pos..].starts_with(' ') => {
+                self.pos = from + len;
+                Ok(s.to_string())
+            },
+            _ => Err(self.error(start, self.src.len(), &format!("{} bytes", len))),
+        }
+    }
+
+    // read the macro holding the contents of a section.
+    fn body(&mut self) -> Result<Vec<PescToken>, PescError> {
+        let start = self.pos;
+        match self.token()? {
+            PescToken::Macro(m) => Ok(m.into_code()),
+            _ => Err(self.error(start, self.pos, "a macro")),
+        }
+    }
+
+    // read a token, and everything inside it if it's a macro, list, or
+    // map. like write_seq, this is a loop rather than being recursive.
+    fn token(&mut self) -> Result<PescToken, PescError> {
+        // the macros, lists, and maps we're inside of (innermost last):
+        // what they are, where they start, how many more tokens they
+        // hold, and the tokens read so far
+        let mut open: Vec<(&'a str, usize, usize, Vec<PescToken>)> = Vec::new();
+
+        loop {
+            let (start, word) = self.expect_word("a token")?;
+
+            let mut done = match word {
+                "macro" | "list" | "map" if open.len() > MAX_NESTING => {
+                    return Err(PescError::new(Some(self.span(start, self.pos)), None,
+                        PescErrorType::LimitExceeded(String::from("nesting"), MAX_NESTING)));
+                },
+                "macro" | "list" | "map" => {
+                    // maps hold a key and a value for each entry
+                    let count = match word {
+                        "map" => self.length()?.saturating_mul(2),
+                        _ => self.length()?,
+                    };
+                    open.push((word, start, count, Vec::new()));
+                    None
+                },
+                _ => Some((start, self.scalar(start, word)?)),
+            };
+
+            // add what was just read to whatever it's inside of,
+            // finishing off anything that's now full
+            loop {
+                let (kind, from, left, items) = match open.last_mut() {
+                    Some(o) => o,
+                    None => return Ok(done.unwrap().1),
+                };
+
+                if let Some((at, t)) = done.take() {
+                    if *kind == "map" && items.len() % 2 == 0 && !matches!(t, PescToken::Str(_)) {
+                        return Err(self.error(at, self.pos, "a string"));
+                    }
+                    items.push(t);
+                    *left -= 1;
+                }
+
+                if *left > 0 {
+                    break;
+                }
+
+                let (kind, from, items) = (*kind, *from, mem::take(items));
+                open.pop();
+                done = Some((from, match kind {
+                    "macro" => PescToken::Macro(items.into()),
+                    "list" => PescToken::List(items.into()),
+                    _ => {
+                        let mut map = BTreeMap::new();
+                        let mut pairs = items.into_iter();
+                        while let (Some(PescToken::Str(k)), Some(v)) = (pairs.next(), pairs.next()) {
+                            map.insert(k.to_string(), v);
+                        }
+                        PescToken::Map(map.into())
+                    },
+                }));
+            }
+        }
+    }
+
+    // read a token that isn't a macro, list, or map.
+    fn scalar(&mut self, start: usize, word: &str) -> Result<PescToken, PescError> {
+        Ok(match word {
+            "int" => PescToken::Number(PescNumber::Int(self.int()?)),
+            "ratio" => {
+                let (num, den) = (self.int()?, self.int()?);
+                match Rational::new(num, den) {
+                    Some(r) => PescToken::Number(PescNumber::from(r)),
+                    None => return Err(self.error(start, self.pos, "a valid fraction")),
+                }
+            },
+            "float" => PescToken::Number(PescNumber::Float(self.hex()?)),
+            "complex" => {
+                let c = Complex::new(self.hex()?, self.hex()?);
+                PescToken::Number(PescNumber::from(c))
+            },
+            "str" => PescToken::Str(self.bytes()?.into()),
+            "fn" => PescToken::Func(self.bytes()?),
+            "sym" => {
+                let (s, sym) = self.expect_word("a symbol")?;
+                let mut chars = sym.chars();
+                let code = sym.strip_prefix("u{").and_then(|h| h.strip_suffix('}'));
+
+                match (chars.next(), chars.next(), code) {
+                    (Some(c), None, _) => PescToken::Symbol(c),
+                    (_, _, Some(h)) => match u32::from_str_radix(h, 16).ok().and_then(char::from_u32) {
+                        Some(c) => PescToken::Symbol(c),
+                        None => return Err(self.error(s, self.pos, "a symbol")),
+                    },
+                    _ => return Err(self.error(s, self.pos, "a symbol")),
+                }
+            },
+            "bool" => match self.expect_word("true or false")? {
+                (_, "true") => PescToken::Bool(true),
+                (_, "false") => PescToken::Bool(false),
+                (s, _) => return Err(self.error(s, self.pos, "true or false")),
+            },
+            _ => return Err(self.error(start, self.pos, "a token")),
+        })
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        let before = &self.src[..start];
+        let line = before.matches('\n').count() + 1;
+        let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        Span { start, end, line, col }
+    }
+
+    fn error(&self, start: usize, end: usize, expected: &str) -> PescError {
+        PescError::new(Some(self.span(start, end)), None,
+            PescErrorType::InvalidSession(expected.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pesc::Pesc;
+
+    #[test]
+    fn test_round_trip() {
+        let mut session = Session {
+            stack: Pesc::parse("1 _2.5 (1+2i) \"a b\nc\" T { [f] { × } } '( 1 '() { } ) '{ \"k\" '{ \"\" 1 } }").unwrap().1,
+            ..Session::default()
+        };
+        session.stack.push(PescToken::Number(PescNumber::Float(0.1)));
+        session.stack.push(PescToken::Symbol('\r'));
+        session.stack.push(PescToken::Symbol('\u{a0}'));
+        session.stack.push(PescToken::Symbol('u'));
+        session.stack.push(PescToken::Number(
+            PescNumber::from(Rational::new(BigInt::from(1_u64), BigInt::from(3_u64)).unwrap())));
+        session.defs.push(("sq".into(), Pesc::parse("\\ *").unwrap().1));
+        session.registers.insert("r".into(), Pesc::parse("\"\"").unwrap().1);
+
+        let saved = session.write();
+        assert_eq!(Session::read(&saved).unwrap(), session);
+        assert_eq!(Session::read(&saved).unwrap().write(), saved);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let e = Session::read("pesc-session 1\nstack\nmacro 2\n  int 1\n  foo\n")
+            .unwrap_err();
+        assert_eq!(e.kind, PescErrorType::InvalidSession("a token".into()));
+        assert_eq!(e.span, Some(Span { start: 39, end: 42, line: 5, col: 3 }));
+
+        // a length too big to add to the position
+        let e = Session::read(&format!("pesc-session 1\nstack\nmacro 1\n  str {} x\n", usize::MAX))
+            .unwrap_err();
+        assert_eq!(e.kind, PescErrorType::InvalidSession(format!("{} bytes", usize::MAX)));
+
+        // values nested too deeply to be used, and map keys that aren't
+        // strings
+        let deep = format!("pesc-session 1\nstack\nmacro 1\n{}list 0\n",
+            "list 1\n".repeat(MAX_NESTING));
+        assert_eq!(Session::read(&deep).unwrap_err().kind,
+            PescErrorType::LimitExceeded(String::from("nesting"), MAX_NESTING));
+        assert!(Session::read(&deep.replacen("list 1\n", "", 1)).is_ok());
+
+        let e = Session::read("pesc-session 1\nstack\nmacro 1\n  map 1\n    list 0\n    int 1\n")
+            .unwrap_err();
+        assert_eq!(e.kind, PescErrorType::InvalidSession("a string".into()));
+        assert_eq!(e.span.unwrap().line, 5);
+    }
+}
@@ -1,20 +1,132 @@
+use std::collections::BTreeMap;
 use std::rc::Rc;
 use std::vec::Vec;
+use crate::bigint::BigInt;
 use crate::errors::*;
+use crate::number::MAX_EXACT_BITS;
+use crate::info::{Category, FuncInfo};
 use crate::pesc::*;
+use crate::pretty;
 use crate::utils::*;
 use crate::rand;
 
 const PESC_EX_E_ITERS: usize = 20;
 
+// the most decimal places [fmt] gives: enough to write out any float
+// in full, and a generous (but cheap) amount for exact numbers.
+const PESC_EX_FMT_FLOAT_PLACES: usize = 1074;
+const PESC_EX_FMT_EXACT_PLACES: usize = 16384;
+
 // --- helper functions ---
 
 macro_rules! rc_box {
     ($x:ident) => (Rc::new(Box::new($x)))
 }
 
+macro_rules! info {
+    ($c:ident, $sig:expr, $doc:expr) => (FuncInfo::new(Category::$c, $sig, $doc))
+}
+
+// check that an index into a sequence (a "list", "string", etc) of
+// `len` items is in bounds; `end` allows an index just past the last
+// item, for slices.
+fn as_index(i: &BigInt, kind: &str, len: usize, end: bool) -> Result<usize, PescErrorType> {
+    match i.to_usize() {
+        Some(u) if u < len || (end && u == len) => Ok(u),
+        _ => Err(PescErrorType::IndexOutOfBounds(
+            String::from(kind), PescNumber::Int(i.clone()), len)),
+    }
+}
+
+// used by functions that can't operate on arbitrarily large integers
+fn as_usize(i: &BigInt) -> Result<usize, PescErrorType> {
+    match i.to_usize() {
+        Some(u) => Ok(u),
+        None => Err(PescErrorType::InvalidArgumentType(
+            String::from("non-negative integer"), i.to_string())),
+    }
+}
+
+// check that a value can be put inside a macro, list, or map without
+// going past MAX_NESTING.
+fn nestable(v: &PescToken) -> Result<(), PescErrorType> {
+    match v.nesting() < MAX_NESTING {
+        true => Ok(()),
+        false => Err(PescErrorType::LimitExceeded(String::from("nesting"), MAX_NESTING)),
+    }
+}
+
+// pop an integer for the bitwise operators, which only make sense
+// for non-negative integers
+fn pop_bits(p: &mut Pesc) -> Result<BigInt, PescErrorType> {
+    let i = p.pop_integer()?;
+    if i.is_negative() {
+        Err(PescErrorType::InvalidArgumentType(
+            String::from("non-negative integer"), i.to_string()))
+    } else {
+        Ok(i)
+    }
+}
+
+// the kinds of sequence the combinators can walk over.
+#[derive(Copy, Clone, PartialEq)]
+enum Seq {
+    Macro, List, Str,
+}
+
+// the elements of a macro or list, or the characters of a string, for
+// the combinators that walk over a sequence.
+fn sequence(v: PescToken) -> Result<(Vec<PescToken>, Seq), PescErrorType> {
+    match v {
+        PescToken::Macro(m) => Ok((m.into_code(), Seq::Macro)),
+        PescToken::List(l) => Ok((unshare(l), Seq::List)),
+        PescToken::Str(s) => Ok((s.chars().map(|c| PescToken::Str(c.to_string().into())).collect(), Seq::Str)),
+        _ => Err(PescErrorType::InvalidArgumentType(
+            String::from("macro/list/string"), v.to_string())),
+    }
+}
+
+// what sequence() was given, for error messages.
+fn seq_name(kind: Seq) -> String {
+    match kind {
+        Seq::Macro => String::from("macro"),
+        Seq::List => String::from("list"),
+        Seq::Str => String::from("string"),
+    }
+}
+
+// the opposite of sequence().
+fn unsequence(items: Vec<PescToken>, kind: Seq) -> Result<PescToken, PescErrorType> {
+    match kind {
+        Seq::Macro => return Ok(PescToken::Macro(items.into())),
+        Seq::List => return Ok(PescToken::List(items.into())),
+        Seq::Str => (),
+    }
+
+    let mut buf = String::new();
+    for item in items {
+        match item {
+            PescToken::Str(s) => buf += &s,
+            _ => return Err(PescErrorType::InvalidArgumentType(
+                String::from("string"), item.to_string())),
+        }
+    }
+
+    Ok(PescToken::Str(buf.into()))
+}
+
+// run a condition without disturbing the stack.
+fn test(p: &mut Pesc, cond: &PescToken) -> Result<bool, PescErrorType> {
+    let v = p.try_peek(cond.clone())?;
+    p.push(v);
+    p.pop_boolean()
+}
+
 // --- declaration ---
 
+/// A list of functions, in the form that Pesc::load takes them.
+pub type PescLibrary<'a> = Vec<(Option<char>, &'a str, Rc<Box<PescFunc>>, FuncInfo)>;
+
 /// Return a list of the functions in the **core** standard library, which
 /// can then be passed to Pesc::load.
 ///
@@ -27,37 +139,60 @@ macro_rules! rc_box {
 /// # Examples
 ///
 /// ```
+/// use pesc::pesc::Pesc;
+/// use pesc::stdlib;
+///
 /// let mut pesc = Pesc::new();
 ///
 /// for func in stdlib::standard() {
-///     pesc.load(func.0, func.1, func.2);
+///     pesc.load(func.0, func.1, func.2, func.3);
 /// }
 /// ```
-pub fn standard<'a>() -> Vec<(Option<char>, &'a str, Rc<Box<PescFunc>>)> {
+pub fn standard<'a>() -> PescLibrary<'a> {
     vec![
-        (Some('+'),  "add",  rc_box!(pesc_add)),
-        (Some('-'),  "sub",  rc_box!(pesc_sub)),
-        (Some('*'),  "mul",  rc_box!(pesc_mul)),
-        (Some('×'),  "mul",  rc_box!(pesc_mul)),
-        (Some('/'),  "div",  rc_box!(pesc_div)),
-        (Some('÷'),  "div",  rc_box!(pesc_div)),
-        (Some('^'),  "pow",  rc_box!(pesc_pow)),
-        (Some('%'),  "mod",  rc_box!(pesc_mod)),
-
-        (Some('\\'), "dup",  rc_box!(pesc_dup)),
-        (Some('$'),  "pop",  rc_box!(pesc_pop)),
-        (Some(','),  "swp",  rc_box!(pesc_swp)),
-        (Some('ø'),  "get",  rc_box!(pesc_get)),
-        (Some('@'),  "rot",  rc_box!(pesc_rot)),
-
-        (Some('&'),  "band", rc_box!(pesc_band)),
-        (Some('~'),  "bnot", rc_box!(pesc_bnot)),
-        (Some('|'),  "bor",  rc_box!(pesc_bor)),
-        (Some('X'),  "bxor", rc_box!(pesc_bxor)),
-        (Some('<'),  "shl",  rc_box!(pesc_bshiftl)),
-        (Some('>'),  "shr",  rc_box!(pesc_bshiftr)),
-
-        (Some(';'),  "run",  rc_box!(pesc_run)),
+        (Some('+'),  "add", rc_box!(pesc_add),
+            info!(Arithmetic, "a:number b:number -- number", "a + b")),
+        (Some('-'),  "sub", rc_box!(pesc_sub),
+            info!(Arithmetic, "a:number b:number -- number", "a - b")),
+        (Some('*'),  "mul", rc_box!(pesc_mul),
+            info!(Arithmetic, "a:number b:number -- number", "a * b")),
+        (Some('×'),  "mul", rc_box!(pesc_mul),
+            info!(Arithmetic, "a:number b:number -- number", "a * b")),
+        (Some('/'),  "div", rc_box!(pesc_div),
+            info!(Arithmetic, "a:number b:number -- number", "a / b")),
+        (Some('÷'),  "div", rc_box!(pesc_div),
+            info!(Arithmetic, "a:number b:number -- number", "a / b")),
+        (Some('^'),  "pow", rc_box!(pesc_pow),
+            info!(Arithmetic, "a:number b:number -- number", "raise a to the power of b")),
+        (Some('%'),  "mod", rc_box!(pesc_mod),
+            info!(Arithmetic, "a:number b:number -- number", "a mod b")),
+
+        (Some('\\'), "dup", rc_box!(pesc_dup),
+            info!(Stack, "a -- a a", "duplicate the first item on the stack")),
+        (Some('$'),  "pop", rc_box!(pesc_pop),
+            info!(Stack, "x:any --", "pop the first item off the stack")),
+        (Some(','),  "swp", rc_box!(pesc_swp),
+            info!(Stack, "a b -- b a", "swap the first two items on the stack")),
+        (Some('ø'),  "get", rc_box!(pesc_get),
+            info!(Stack, "n:number -- any", "copy the nth item on the stack to the top")),
+        (Some('@'),  "rot", rc_box!(pesc_rot),
+            info!(Stack, "n:number --", "swap the nth item on the stack with the first")),
+
+        (Some('&'),  "band", rc_box!(pesc_band),
+            info!(Bitwise, "a:number b:number -- number", "a AND b")),
+        (Some('~'),  "bnot", rc_box!(pesc_bnot),
+            info!(Bitwise, "x:number -- number", "NOT x")),
+        (Some('|'),  "bor", rc_box!(pesc_bor),
+            info!(Bitwise, "a:number b:number -- number", "a OR b")),
+        (Some('X'),  "bxor", rc_box!(pesc_bxor),
+            info!(Bitwise, "a:number b:number -- number", "a XOR b")),
+        (Some('<'),  "shl", rc_box!(pesc_bshiftl),
+            info!(Bitwise, "a:number b:number -- number", "a << b")),
+        (Some('>'),  "shr", rc_box!(pesc_bshiftr),
+            info!(Bitwise, "a:number b:number -- number", "a >> b")),
+
+        (Some(';'),  "run", rc_box!(pesc_run),
+            info!(Misc, "f:callable -- ...", "execute f")),
     ]
 }
 
@@ -69,65 +204,260 @@ pub fn standard<'a>() -> Vec<(Option<char>, &'a str, Rc<Box<PescFunc>>)> {
 /// # Examples
 ///
 /// ```
+/// use pesc::pesc::Pesc;
+/// use pesc::stdlib;
+///
 /// let mut pesc = Pesc::new();
 ///
 /// for func in stdlib::extended() {
-///     pesc.load(func.0, func.1, func.2);
+///     pesc.load(func.0, func.1, func.2, func.3);
 /// }
 /// ```
-pub fn extended<'a>() -> Vec<(Option<char>, &'a str, Rc<Box<PescFunc>>)> {
+pub fn extended<'a>() -> PescLibrary<'a> {
     vec![
-        (Some('!'), "neg",     rc_box!(pesc_b_neg)),
-        (None,      "and",     rc_box!(pesc_b_and)),
-        (None,      "or",      rc_box!(pesc_b_or)),
-        (None,      "eq?",     rc_box!(pesc_b_eq)),
-        (None,      "gt?",     rc_box!(pesc_b_gt)),
-        (None,      "gte",     rc_box!(pesc_ex_gte)),
-        (None,      "lt?",     rc_box!(pesc_b_lt)),
-        (None,      "lte",     rc_box!(pesc_ex_lte)),
-
-        (Some('?'), "if?",     rc_box!(pesc_b_cond)),
-
-        (None,      "def",     rc_box!(pesc_ex_def)),
-        (Some('s'), "size",    rc_box!(pesc_ex_size)),
-        (Some('r'), "rand",    rc_box!(pesc_ex_rand)),
-
-        (None,      "sin",     rc_box!(pesc_ex_sin)),
-        (None,      "cos",     rc_box!(pesc_ex_cos)),
-        (None,      "tan",     rc_box!(pesc_ex_tan)),
-        (None,      "csc",     rc_box!(pesc_ex_csc)),
-        (None,      "sec",     rc_box!(pesc_ex_sec)),
-        (None,      "cot",     rc_box!(pesc_ex_cot)),
-        (None,      "atan",    rc_box!(pesc_ex_atan)),
-
-        (Some('l'), "log",     rc_box!(pesc_ex_log)),
-        (None,      "sqrt",    rc_box!(pesc_ex_sqrt)),
-        (None,      "cbrt",    rc_box!(pesc_ex_cbrt)),
-        (None,      "fact",    rc_box!(pesc_ex_fact)),
-        (Some('A'), "ack",     rc_box!(pesc_ex_ack)),
-        (Some('a'), "abs",     rc_box!(pesc_ex_abs)),
-        (None,      "lcm",     rc_box!(pesc_ex_lcm)),
-        (None,      "gcd",     rc_box!(pesc_ex_gcd)),
-
-        (Some('p'), "pi",      rc_box!(pesc_ex_pi)),
-        (Some('e'), "e",       rc_box!(pesc_ex_e)),
-
-        (Some('m'), "min",     rc_box!(pesc_ex_min)),
-        (Some('M'), "max",     rc_box!(pesc_ex_max)),
-        (Some('c'), "clamp",   rc_box!(pesc_ex_clamp)),
-
-        (None,      "floor",   rc_box!(pesc_ex_floor)),
-        (None,      "ceil",    rc_box!(pesc_ex_ceil)),
-        (None,      "round",   rc_box!(pesc_ex_round)),
-
-        (None,      "frrn",    rc_box!(pesc_ex_frrn)),
-        (None,      "torn",    rc_box!(pesc_ex_torn)),
-
-        (None,      "odd",     rc_box!(pesc_ex_odd)),
-        (None,      "even",    rc_box!(pesc_ex_even)),
-
-        (None,      "coprime", rc_box!(pesc_ex_coprime)),
-        (None,      "prime",   rc_box!(pesc_ex_prime)),
+        (Some('!'), "neg", rc_box!(pesc_b_neg),
+            info!(Boolean, "x:bool -- bool", "negate x")),
+        (None,      "and", rc_box!(pesc_b_and),
+            info!(Boolean, "a:bool b:bool -- bool", "whether a and b are both true")),
+        (None,      "or", rc_box!(pesc_b_or),
+            info!(Boolean, "a:bool b:bool -- bool", "whether either a or b is true")),
+        (None,      "eq?", rc_box!(pesc_b_eq),
+            info!(Boolean, "a:any b:any -- bool", "whether a and b are equal")),
+        (None,      "gt?", rc_box!(pesc_b_gt),
+            info!(Boolean, "a:number b:number -- bool", "whether b > a")),
+        (None,      "gte", rc_box!(pesc_ex_gte),
+            info!(Boolean, "a:number b:number -- bool", "whether b >= a")),
+        (None,      "lt?", rc_box!(pesc_b_lt),
+            info!(Boolean, "a:number b:number -- bool", "whether b < a")),
+        (None,      "lte", rc_box!(pesc_ex_lte),
+            info!(Boolean, "a:number b:number -- bool", "whether b <= a")),
+
+        (Some('?'), "if?", rc_box!(pesc_b_cond),
+            info!(Boolean, "else:callable main:callable cond:bool -- ...",
+                "execute main if cond is true, and else otherwise")),
+
+        (None,      "times", rc_box!(pesc_ex_times),
+            info!(Combinator, "n:number p:callable -- ...", "execute p n times")),
+        (None,      "while", rc_box!(pesc_ex_while),
+            info!(Combinator, "cond:callable p:callable -- ...",
+                "execute p for as long as cond is true")),
+        (None,      "dip", rc_box!(pesc_ex_dip),
+            info!(Combinator, "x:any p:callable -- ...",
+                "set aside x, execute p, and push x back")),
+        (None,      "linrec", rc_box!(pesc_ex_linrec),
+            info!(Combinator, "cond:callable then:callable r1:callable r2:callable -- ...",
+                "if cond is true, execute then; otherwise execute r1, recurse, and execute r2")),
+        (None,      "binrec", rc_box!(pesc_ex_binrec),
+            info!(Combinator, "cond:callable then:callable r1:callable r2:callable -- ...",
+                "if cond is true, execute then; otherwise split with r1, recurse on each part, join with r2")),
+        (None,      "primrec", rc_box!(pesc_ex_primrec),
+            info!(Combinator, "x:any init:callable p:callable -- ...",
+                "push x down to 1 (or the values of x), execute init, and execute p for each")),
+        (None,      "step", rc_box!(pesc_ex_step),
+            info!(Combinator, "s:seq p:callable -- ...",
+                "push each value of s and execute p on it")),
+        (None,      "map", rc_box!(pesc_ex_map),
+            info!(Combinator, "s:seq p:callable -- seq",
+                "execute p on each value of s, and collect the results")),
+        (None,      "filter", rc_box!(pesc_ex_filter),
+            info!(Combinator, "s:seq cond:callable -- seq",
+                "keep the values of s that cond is true for")),
+        (None,      "fold", rc_box!(pesc_ex_fold),
+            info!(Combinator, "s:seq init:any p:callable -- ...",
+                "push init, then push each value of s and execute p on it")),
+
+        (None,      "list", rc_box!(pesc_ex_list),
+            info!(List, "... n:number -- list", "pop n values into a list")),
+        (None,      "nth", rc_box!(pesc_ex_nth),
+            info!(List, "l:list i:number -- any", "the value at index i of l, starting at 0")),
+        (None,      "slice", rc_box!(pesc_ex_slice),
+            info!(List, "l:list start:number end:number -- list",
+                "the values of l from index start up to (but not including) end")),
+        (None,      "len", rc_box!(pesc_ex_len),
+            info!(List, "s:seq -- number",
+                "the number of values in the list or macro s, or characters in the string s")),
+        (None,      "concat", rc_box!(pesc_ex_concat),
+            info!(List, "a:seq b:seq -- seq",
+                "the values of a followed by those of b, which must be the same kind of seq")),
+        (None,      "cons", rc_box!(pesc_ex_cons),
+            info!(List, "x:any s:seq -- seq", "s with x added to the front")),
+        (None,      "uncons", rc_box!(pesc_ex_uncons),
+            info!(List, "s:seq -- any seq", "the first value of s, and the rest of s")),
+        (None,      "first", rc_box!(pesc_ex_first),
+            info!(List, "s:seq -- any", "the first value of s")),
+        (None,      "rest", rc_box!(pesc_ex_rest),
+            info!(List, "s:seq -- seq", "s without its first value")),
+        (None,      "reverse", rc_box!(pesc_ex_reverse),
+            info!(List, "l:list -- list", "l backwards")),
+        (None,      "explode", rc_box!(pesc_ex_explode),
+            info!(List, "l:list -- ...", "push each value of l onto the stack")),
+
+        (None,      "at", rc_box!(pesc_ex_at),
+            info!(Map, "m:map k:string -- any", "the value of key k in m")),
+        (None,      "put", rc_box!(pesc_ex_put),
+            info!(Map, "m:map k:string v:any -- map", "m with key k set to v")),
+        (None,      "has", rc_box!(pesc_ex_has),
+            info!(Map, "m:map k:string -- bool", "whether m has the key k")),
+        (None,      "keys", rc_box!(pesc_ex_keys),
+            info!(Map, "m:map -- list", "the keys of m, in sorted order")),
+        (None,      "values", rc_box!(pesc_ex_values),
+            info!(Map, "m:map -- list", "the values of m, in the order of their keys")),
+        (None,      "del", rc_box!(pesc_ex_del),
+            info!(Map, "m:map k:string -- map", "m without the key k")),
+
+        (None,      "substr", rc_box!(pesc_ex_substr),
+            info!(String, "s:string start:number end:number -- string",
+                "the characters of s from index start up to (but not including) end")),
+        (None,      "split", rc_box!(pesc_ex_split),
+            info!(String, "s:string sep:string -- list",
+                "the parts of s between each sep, or its characters if sep is empty")),
+        (None,      "join", rc_box!(pesc_ex_join),
+            info!(String, "l:list sep:string -- string", "the strings in l, with sep between each")),
+        (None,      "trim", rc_box!(pesc_ex_trim),
+            info!(String, "s:string -- string", "s without whitespace at either end")),
+        (None,      "upper", rc_box!(pesc_ex_upper),
+            info!(String, "s:string -- string", "s in uppercase")),
+        (None,      "lower", rc_box!(pesc_ex_lower),
+            info!(String, "s:string -- string", "s in lowercase")),
+        (None,      "find", rc_box!(pesc_ex_find),
+            info!(String, "s:string t:string -- number",
+                "the index of the first t in s, or -1 if there isn't one")),
+        (None,      "replace", rc_box!(pesc_ex_replace),
+            info!(String, "s:string from:string to:string -- string",
+                "s with every from replaced by to")),
+        (None,      "repeat", rc_box!(pesc_ex_repeat),
+            info!(String, "s:string n:number -- string", "s, n times over")),
+        (None,      "str", rc_box!(pesc_ex_str),
+            info!(String, "x:number -- string", "x as a string")),
+        (None,      "num", rc_box!(pesc_ex_num),
+            info!(String, "s:string -- number", "the number written in s, such as \"_1.5\" or \"0xff\"")),
+        (None,      "fmt", rc_box!(pesc_ex_fmt),
+            info!(String, "x:number f:string -- string",
+                "x as a string in the format f: \".2\", \"e\", \".2e\", \"x\", \"o\", or \"b\"")),
+
+        (None,      "def", rc_box!(pesc_ex_def),
+            info!(Misc, "body:macro name:string --",
+                "define a function called name that executes body")),
+        (Some('s'), "size", rc_box!(pesc_ex_size),
+            info!(Misc, "-- number", "push the size of the stack")),
+        (Some('r'), "rand", rc_box!(pesc_ex_rand),
+            info!(Misc, "-- number", "push a random number")),
+
+        (None,      "parse", rc_box!(pesc_ex_parse),
+            info!(Misc, "s:string -- macro", "a macro of the code in s")),
+        (None,      "quote", rc_box!(pesc_ex_quote),
+            info!(Misc, "m:macro -- string",
+                "the code in m, as a string that [parse] turns back into m")),
+        (None,      "quote1", rc_box!(pesc_ex_quote1),
+            info!(Misc, "x:any -- macro", "a macro of just x, which pushes x (or calls it)")),
+        (None,      "unquote", rc_box!(pesc_ex_unquote),
+            info!(Misc, "s:string -- ...", "execute the code in s")),
+
+        (None,      "help", rc_box!(pesc_ex_help),
+            info!(Misc, "name:string --", "print what the function called name does")),
+        (None,      "words", rc_box!(pesc_ex_words),
+            info!(Misc, "--", "print the name of every function, and its operators")),
+        (None,      "see", rc_box!(pesc_ex_see),
+            info!(Misc, "name:string --", "print the source of a function defined with [def]")),
+        (None,      "dbg", rc_box!(pesc_ex_dbg),
+            info!(Misc, "a -- a", "print the source of the first item on the stack")),
+
+        (None,      "store", rc_box!(pesc_ex_store),
+            info!(Register, "x:any r:string --", "replace the top value of register r with x")),
+        (None,      "load", rc_box!(pesc_ex_load),
+            info!(Register, "r:string -- any", "push a copy of the top value of register r")),
+        (None,      "rpush", rc_box!(pesc_ex_rpush),
+            info!(Register, "x:any r:string --", "push x onto register r")),
+        (None,      "rpop", rc_box!(pesc_ex_rpop),
+            info!(Register, "r:string -- any",
+                "pop the top value of register r onto the stack")),
+
+        (None,      "undo", rc_box!(pesc_ex_undo),
+            info!(History, "... -- ...",
+                "go back to the way things were before the previous input")),
+        (None,      "redo", rc_box!(pesc_ex_redo),
+            info!(History, "... -- ...", "redo an input that was undone with [undo]")),
+
+        (None,      "sin", rc_box!(pesc_ex_sin),
+            info!(Trigonometry, "radians:number -- number", "sin(radians)")),
+        (None,      "cos", rc_box!(pesc_ex_cos),
+            info!(Trigonometry, "radians:number -- number", "cos(radians)")),
+        (None,      "tan", rc_box!(pesc_ex_tan),
+            info!(Trigonometry, "radians:number -- number", "tan(radians)")),
+        (None,      "csc", rc_box!(pesc_ex_csc),
+            info!(Trigonometry, "radians:number -- number", "1 / sin(radians)")),
+        (None,      "sec", rc_box!(pesc_ex_sec),
+            info!(Trigonometry, "radians:number -- number", "1 / cos(radians)")),
+        (None,      "cot", rc_box!(pesc_ex_cot),
+            info!(Trigonometry, "radians:number -- number", "1 / tan(radians)")),
+        (None,      "atan", rc_box!(pesc_ex_atan),
+            info!(Trigonometry, "x:number -- number", "atan(x), in radians")),
+
+        (Some('l'), "log", rc_box!(pesc_ex_log),
+            info!(Math, "x:number base:number -- number",
+                "the logarithm of x with respect to base")),
+        (None,      "sqrt", rc_box!(pesc_ex_sqrt),
+            info!(Math, "x:number -- number", "the square root of x")),
+        (None,      "cbrt", rc_box!(pesc_ex_cbrt),
+            info!(Math, "x:number -- number", "the cube root of x")),
+        (None,      "fact", rc_box!(pesc_ex_fact),
+            info!(Math, "x:number -- number", "the factorial of x")),
+        (Some('A'), "ack", rc_box!(pesc_ex_ack),
+            info!(Math, "m:number n:number -- number", "Ackermann's function on m and n")),
+        (Some('a'), "abs", rc_box!(pesc_ex_abs),
+            info!(Math, "x:number -- number", "the absolute value (or modulus) of x")),
+        (None,      "lcm", rc_box!(pesc_ex_lcm),
+            info!(Math, "a:number b:number -- number",
+                "the lowest common multiple of a and b")),
+        (None,      "gcd", rc_box!(pesc_ex_gcd),
+            info!(Math, "u:number v:number -- number",
+                "the greatest common divisor of u and v")),
+
+        (Some('p'), "pi", rc_box!(pesc_ex_pi),
+            info!(Math, "-- number", "push pi")),
+        (Some('e'), "e", rc_box!(pesc_ex_e),
+            info!(Math, "-- number", "push Euler's number")),
+
+        (Some('m'), "min", rc_box!(pesc_ex_min),
+            info!(Math, "a:number b:number -- number", "the smaller of a and b")),
+        (Some('M'), "max", rc_box!(pesc_ex_max),
+            info!(Math, "a:number b:number -- number", "the larger of a and b")),
+        (Some('c'), "clamp", rc_box!(pesc_ex_clamp),
+            info!(Math, "max:number min:number x:number -- number",
+                "x, kept between min and max")),
+
+        (None,      "re", rc_box!(pesc_ex_re),
+            info!(Math, "x:number -- number", "the real part of x")),
+        (None,      "im", rc_box!(pesc_ex_im),
+            info!(Math, "x:number -- number", "the imaginary part of x")),
+        (None,      "arg", rc_box!(pesc_ex_arg),
+            info!(Math, "x:number -- number", "the argument (phase angle) of x, in radians")),
+        (None,      "conj", rc_box!(pesc_ex_conj),
+            info!(Math, "x:number -- number", "the complex conjugate of x")),
+
+        (None,      "floor", rc_box!(pesc_ex_floor),
+            info!(Math, "x:number -- number", "round x down")),
+        (None,      "ceil", rc_box!(pesc_ex_ceil),
+            info!(Math, "x:number -- number", "round x up")),
+        (None,      "round", rc_box!(pesc_ex_round),
+            info!(Math, "x:number -- number", "round x to the nearest integer")),
+        (None,      "dec", rc_box!(pesc_ex_dec),
+            info!(Math, "x:number -- number", "a floating point approximation of x")),
+
+        (None,      "frrn", rc_box!(pesc_ex_frrn),
+            info!(Math, "x:string -- number", "convert the roman numeral x to a number")),
+        (None,      "torn", rc_box!(pesc_ex_torn),
+            info!(Math, "x:number -- string", "convert x to roman numerals")),
+
+        (None,      "odd", rc_box!(pesc_ex_odd),
+            info!(Math, "x:number -- bool", "whether x is odd")),
+        (None,      "even", rc_box!(pesc_ex_even),
+            info!(Math, "x:number -- bool", "whether x is even")),
+
+        (None,      "coprime", rc_box!(pesc_ex_coprime),
+            info!(Math, "u:number v:number -- bool", "whether u and v are coprime")),
+        (None,      "prime", rc_box!(pesc_ex_prime),
+            info!(Math, "x:number -- bool", "whether x is prime")),
     ]
 }
 
@@ -137,7 +467,7 @@ fn pesc_add(p: &mut Pesc) -> Result<(), PescErrorType> {
     let b = p.pop_number()?;
     let a = p.pop_number()?;
 
-    p.push(PescToken::Number(a + b));
+    p.push(PescToken::Number(&a + &b));
     Ok(())
 }
 
@@ -145,7 +475,7 @@ fn pesc_sub(p: &mut Pesc) -> Result<(), PescErrorType> {
     let b = p.pop_number()?;
     let a = p.pop_number()?;
 
-    p.push(PescToken::Number(a - b));
+    p.push(PescToken::Number(&a - &b));
     Ok(())
 }
 
@@ -153,7 +483,7 @@ fn pesc_mul(p: &mut Pesc) -> Result<(), PescErrorType> {
     let b = p.pop_number()?;
     let a = p.pop_number()?;
 
-    p.push(PescToken::Number(a * b));
+    p.push(PescToken::Number(&a * &b));
     Ok(())
 }
 
@@ -161,10 +491,10 @@ fn pesc_div(p: &mut Pesc) -> Result<(), PescErrorType> {
     let b = p.pop_number()?;
     let a = p.pop_number()?;
 
-    if b == 0_f64 {
+    if b.is_zero() {
         Err(PescErrorType::DivideByZero(a, b))
     } else {
-        p.push(PescToken::Number(a / b));
+        p.push(PescToken::Number(&a / &b));
         Ok(())
     }
 }
@@ -173,7 +503,7 @@ fn pesc_pow(p: &mut Pesc) -> Result<(), PescErrorType> {
     let b = p.pop_number()?;
     let a = p.pop_number()?;
 
-    p.push(PescToken::Number(a.powf(b)));
+    p.push(PescToken::Number(a.pow(&b)));
     Ok(())
 }
 
@@ -181,10 +511,10 @@ fn pesc_mod(p: &mut Pesc) -> Result<(), PescErrorType> {
     let b = p.pop_number()?;
     let a = p.pop_number()?;
 
-    if b == 0_f64 {
+    if b.is_zero() {
         Err(PescErrorType::DivideByZero(a, b))
     } else {
-        p.push(PescToken::Number(a % b));
+        p.push(PescToken::Number(&a % &b));
         Ok(())
     }
 }
@@ -222,10 +552,10 @@ fn pesc_get(p: &mut Pesc) -> Result<(), PescErrorType> {
 fn pesc_rot(p: &mut Pesc) -> Result<(), PescErrorType> {
     // swap the nth item on the stack with the first item
     let idx   = p.pop_number()?;
-    let nth   = p.nth_ref(idx)?.clone();
-    let first = p.nth_ref(0.0)?.clone();
+    let nth   = p.nth_ref(idx.clone())?.clone();
+    let first = p.nth_ref(PescNumber::from(0_usize))?.clone();
 
-    p.set(0.0,   nth)?;
+    p.set(PescNumber::from(0_usize), nth)?;
     p.set(idx, first)?;
     Ok(())
 }
@@ -263,16 +593,16 @@ fn pesc_b_eq(p: &mut Pesc) -> Result<(), PescErrorType> {
 }
 
 fn pesc_b_gt(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()?;
-    let a = p.pop_number()?;
+    let b = p.pop_real()?;
+    let a = p.pop_real()?;
 
     p.push(PescToken::Bool(a < b));
     Ok(())
 }
 
 fn pesc_b_lt(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()?;
-    let a = p.pop_number()?;
+    let b = p.pop_real()?;
+    let a = p.pop_real()?;
 
     p.push(PescToken::Bool(a > b));
     Ok(())
@@ -284,24 +614,22 @@ fn pesc_b_cond(p: &mut Pesc) -> Result<(), PescErrorType> {
     let else_branch = p.pop()?;
 
     match cond {
-        true  => p.try_exec(main_branch)?,
-        false => p.try_exec(else_branch)?,
+        true  => p.schedule(main_branch),
+        false => p.schedule(else_branch),
     }
-
-    Ok(())
 }
 
 fn pesc_ex_lte(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()?;
-    let a = p.pop_number()?;
+    let b = p.pop_real()?;
+    let a = p.pop_real()?;
 
     p.push(PescToken::Bool(a >= b));
     Ok(())
 }
 
 fn pesc_ex_gte(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()?;
-    let a = p.pop_number()?;
+    let b = p.pop_real()?;
+    let a = p.pop_real()?;
 
     p.push(PescToken::Bool(a <= b));
     Ok(())
@@ -311,67 +639,126 @@ fn pesc_ex_def(p: &mut Pesc) -> Result<(), PescErrorType> {
     let name = p.pop_string()?;
     let body = p.pop_macro()?;
 
-    p.funcs.insert(name, Rc::new(Box::new(move |p|
-                p.try_exec(PescToken::Macro(body.clone())))));
+    p.define(&name, body);
     Ok(())
 }
 
 fn pesc_ex_size(p: &mut Pesc) -> Result<(), PescErrorType> {
-    p.push(PescToken::Number(p.stack.len() as f64));
+    p.push(PescToken::Number(PescNumber::from(p.stack.len())));
     Ok(())
 }
 
 fn pesc_ex_rand(p: &mut Pesc) -> Result<(), PescErrorType> {
     // TODO: random decimal, no first zero
-    let r = unsafe { rand::lrand48() } as f64;
-    p.push(PescToken::Number(r));
+    let r = unsafe { rand::lrand48() } as i64;
+    p.push(PescToken::Number(PescNumber::from(r)));
     Ok(())
 }
 
+// --- register functions ---
+
+fn pesc_ex_store(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let reg = p.pop_string()?;
+    let v = p.pop()?;
+
+    p.reg_store(&reg, v);
+    Ok(())
+}
+
+fn pesc_ex_load(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let reg = p.pop_string()?;
+    let v = p.reg_load(&reg)?.clone();
+
+    p.push(v);
+    Ok(())
+}
+
+fn pesc_ex_rpush(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let reg = p.pop_string()?;
+    let v = p.pop()?;
+
+    p.reg_push(&reg, v);
+    Ok(())
+}
+
+fn pesc_ex_rpop(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let reg = p.pop_string()?;
+    let v = p.reg_pop(&reg)?;
+
+    p.push(v);
+    Ok(())
+}
+
+// --- history functions ---
+
+fn pesc_ex_undo(p: &mut Pesc) -> Result<(), PescErrorType> {
+    if p.undo() {
+        Ok(())
+    } else {
+        Err(PescErrorType::Other(String::from("There's nothing to undo.")))
+    }
+}
+
+fn pesc_ex_redo(p: &mut Pesc) -> Result<(), PescErrorType> {
+    if p.redo() {
+        Ok(())
+    } else {
+        Err(PescErrorType::Other(String::from("There's nothing to redo.")))
+    }
+}
+
 fn pesc_band(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()? as usize;
-    let a = p.pop_number()? as usize;
+    let b = pop_bits(p)?;
+    let a = pop_bits(p)?;
 
-    p.push(PescToken::Number((a & b) as f64));
+    p.push(PescToken::Number(PescNumber::from(a.and(&b))));
     Ok(())
 }
 
 fn pesc_bnot(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let x = p.pop_number()? as usize;
+    // !x == -x - 1 in two's complement
+    let x = p.pop_integer()?;
 
-    p.push(PescToken::Number(!x as f64));
+    p.push(PescToken::Number(PescNumber::from(-&x - BigInt::one())));
     Ok(())
 }
 
 fn pesc_bor(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()? as usize;
-    let a = p.pop_number()? as usize;
+    let b = pop_bits(p)?;
+    let a = pop_bits(p)?;
 
-    p.push(PescToken::Number((a | b) as f64));
+    p.push(PescToken::Number(PescNumber::from(a.or(&b))));
     Ok(())
 }
 
 fn pesc_bxor(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()? as usize;
-    let a = p.pop_number()? as usize;
+    let b = pop_bits(p)?;
+    let a = pop_bits(p)?;
 
-    p.push(PescToken::Number((a ^ b) as f64));
+    p.push(PescToken::Number(PescNumber::from(a.xor(&b))));
     Ok(())
 }
 
 fn pesc_bshiftr(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()? as usize;
-    let a = p.pop_number()? as usize;
+    let b = as_usize(&p.pop_integer()?)?;
+    let a = pop_bits(p)?;
 
-    p.push(PescToken::Number((a >> b) as f64));
+    p.push(PescToken::Number(PescNumber::from(a.shr(b))));
     Ok(())
 }
 
 fn pesc_bshiftl(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()? as usize;
-    let a = p.pop_number()? as usize;
+    let b = as_usize(&p.pop_integer()?)?;
+    let a = pop_bits(p)?;
+
+    // like [pow], give up on exactness for huge results
+    let n = match a.bits().saturating_add(b) {
+        bits if bits > MAX_EXACT_BITS && !a.is_zero() =>
+            PescNumber::Float(a.to_f64() * 2_f64.powf(b as f64)),
+        _ => PescNumber::from(a.shl(b)),
+    };
 
-    p.push(PescToken::Number((a << b) as f64));
+    p.push(PescToken::Number(n));
     Ok(())
 }
 
@@ -399,28 +786,28 @@ fn pesc_ex_tan(p: &mut Pesc) -> Result<(), PescErrorType> {
 fn pesc_ex_sec(p: &mut Pesc) -> Result<(), PescErrorType> {
     let a = p.pop_number()?;
 
-    p.push(PescToken::Number(1_f64 / a.cos()));
+    p.push(PescToken::Number(&PescNumber::from(1_f64) / &a.cos()));
     Ok(())
 }
 
 fn pesc_ex_csc(p: &mut Pesc) -> Result<(), PescErrorType> {
     let a = p.pop_number()?;
 
-    p.push(PescToken::Number(1_f64 / a.sin()));
+    p.push(PescToken::Number(&PescNumber::from(1_f64) / &a.sin()));
     Ok(())
 }
 
 fn pesc_ex_cot(p: &mut Pesc) -> Result<(), PescErrorType> {
     let a = p.pop_number()?;
 
-    p.push(PescToken::Number(1_f64 / a.tan()));
+    p.push(PescToken::Number(&PescNumber::from(1_f64) / &a.tan()));
     Ok(())
 }
 
 fn pesc_ex_atan(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let a = p.pop_number()?;
+    let a = p.pop_real()?.to_f64();
 
-    p.push(PescToken::Number(a.atan()));
+    p.push(PescToken::Number(PescNumber::from(a.atan())));
     Ok(())
 }
 
@@ -428,40 +815,40 @@ fn pesc_ex_log(p: &mut Pesc) -> Result<(), PescErrorType> {
     let b = p.pop_number()?;
     let a = p.pop_number()?;
 
-    p.push(PescToken::Number(a.log(b)));
+    p.push(PescToken::Number(a.log(&b)));
     Ok(())
 }
 
 fn pesc_ex_pi(p: &mut Pesc) -> Result<(), PescErrorType> {
-    p.push(PescToken::Number(pi()));
+    p.push(PescToken::Number(PescNumber::from(pi())));
     Ok(())
 }
 
 fn pesc_ex_e(p: &mut Pesc) -> Result<(), PescErrorType> {
-    p.push(PescToken::Number(e(PESC_EX_E_ITERS)));
+    p.push(PescToken::Number(PescNumber::from(e(PESC_EX_E_ITERS))));
     Ok(())
 }
 
 fn pesc_ex_min(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()?;
-    let a = p.pop_number()?;
+    let b = p.pop_real()?;
+    let a = p.pop_real()?;
 
     p.push(PescToken::Number(if a < b { a } else { b }));
     Ok(())
 }
 
 fn pesc_ex_max(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()?;
-    let a = p.pop_number()?;
+    let b = p.pop_real()?;
+    let a = p.pop_real()?;
 
     p.push(PescToken::Number(if a > b { a } else { b }));
     Ok(())
 }
 
 fn pesc_ex_clamp(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let val = p.pop_number()?;
-    let min = p.pop_number()?;
-    let max = p.pop_number()?;
+    let val = p.pop_real()?;
+    let min = p.pop_real()?;
+    let max = p.pop_real()?;
 
     let res = match () {
         _ if val < min => min,
@@ -481,42 +868,100 @@ fn pesc_ex_sqrt(p: &mut Pesc) -> Result<(), PescErrorType> {
 }
 
 fn pesc_ex_cbrt(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let v = p.pop_real()?.to_f64();
+
+    p.push(PescToken::Number(PescNumber::from(v.cbrt())));
+    Ok(())
+}
+
+fn pesc_ex_re(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let v = p.pop_number()?;
+
+    let re = match v {
+        PescNumber::Complex(c) => PescNumber::from(c.re),
+        _ => v,
+    };
+
+    p.push(PescToken::Number(re));
+    Ok(())
+}
+
+fn pesc_ex_im(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let v = p.pop_number()?;
+
+    let im = match v {
+        PescNumber::Complex(c) => PescNumber::from(c.im),
+        _ => PescNumber::from(0_usize),
+    };
+
+    p.push(PescToken::Number(im));
+    Ok(())
+}
+
+fn pesc_ex_arg(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let v = p.pop_number()?;
+
+    p.push(PescToken::Number(PescNumber::from(v.to_complex().arg())));
+    Ok(())
+}
+
+fn pesc_ex_conj(p: &mut Pesc) -> Result<(), PescErrorType> {
     let v = p.pop_number()?;
 
-    p.push(PescToken::Number(v.cbrt()));
+    let conj = match v {
+        PescNumber::Complex(c) => PescNumber::from(c.conj()),
+        _ => v,
+    };
+
+    p.push(PescToken::Number(conj));
     Ok(())
 }
 
 fn pesc_ex_fact(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let v = p.pop_number()? as usize;
+    let v = as_usize(&p.pop_integer()?)?;
 
-    p.push(PescToken::Number(factorial(v) as f64));
+    // anything too big to compute exactly is too big for a float, too
+    let n = match factorial(v, || p.charge(1))? {
+        Some(n) => PescNumber::from(n),
+        None => PescNumber::Float(f64::INFINITY),
+    };
+
+    p.push(PescToken::Number(n));
     Ok(())
 }
 
 fn pesc_ex_ceil(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let v = p.pop_number()?;
+    let v = p.pop_real()?;
 
     p.push(PescToken::Number(v.ceil()));
     Ok(())
 }
 
 fn pesc_ex_floor(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let v = p.pop_number()?;
+    let v = p.pop_real()?;
 
     p.push(PescToken::Number(v.floor()));
     Ok(())
 }
 
 fn pesc_ex_round(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let v = p.pop_number()?;
+    let v = p.pop_real()?;
 
     p.push(PescToken::Number(v.round()));
     Ok(())
 }
 
+fn pesc_ex_dec(p: &mut Pesc) -> Result<(), PescErrorType> {
+    // give up on exactness, and get a plain old decimal approximation
+    let v = p.pop_real()?.to_f64();
+
+    p.push(PescToken::Number(PescNumber::from(v)));
+    Ok(())
+}
+
 fn pesc_ex_torn(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let mut v = p.pop_number()?.round() as usize;
+    let mut v = as_usize(&p.pop_real()?.round()
+        .to_int().unwrap_or_default())?;
     let mut buf: Vec<char> = Vec::new();
 
     while v != 0 {
@@ -532,7 +977,7 @@ fn pesc_ex_torn(p: &mut Pesc) -> Result<(), PescErrorType> {
         }
     }
 
-    p.push(PescToken::Str(buf.iter().collect::<String>()));
+    p.push(PescToken::Str(buf.iter().collect::<String>().into()));
     Ok(())
 }
 
@@ -548,45 +993,46 @@ fn pesc_ex_frrn(p: &mut Pesc) -> Result<(), PescErrorType> {
         ctr += 1;
     }
 
-    p.push(PescToken::Number(buf as f64));
+    p.push(PescToken::Number(PescNumber::from(buf)));
     Ok(())
 }
 
 fn pesc_ex_gcd(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let v = p.pop_number()? as usize;
-    let u = p.pop_number()? as usize;
+    let v = p.pop_integer()?;
+    let u = p.pop_integer()?;
 
-    p.push(PescToken::Number(gcd(u, v) as f64));
+    p.push(PescToken::Number(PescNumber::from(gcd(&u, &v))));
     Ok(())
 }
 
 fn pesc_ex_lcm(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let b = p.pop_number()? as usize;
-    let a = p.pop_number()? as usize;
+    let b = p.pop_integer()?;
+    let a = p.pop_integer()?;
 
-    p.push(PescToken::Number(lcm(a, b) as f64));
+    p.push(PescToken::Number(PescNumber::from(lcm(&a, &b))));
     Ok(())
 }
 
 fn pesc_ex_ack(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let n = p.pop_number()? as usize;
-    let m = p.pop_number()? as usize;
+    let n = as_usize(&p.pop_integer()?)?;
+    let m = as_usize(&p.pop_integer()?)?;
 
-    p.push(PescToken::Number(ackermann(m, n) as f64));
+    let a = ackermann(m, n, || p.charge(1))?;
+    p.push(PescToken::Number(PescNumber::from(a)));
     Ok(())
 }
 
 fn pesc_ex_odd(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let v = p.pop_number()? as usize;
+    let v = p.pop_integer()?;
 
-    p.push(PescToken::Bool(v & 1 == 1));
+    p.push(PescToken::Bool(v.is_odd()));
     Ok(())
 }
 
 fn pesc_ex_even(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let v = p.pop_number()? as usize;
+    let v = p.pop_integer()?;
 
-    p.push(PescToken::Bool(v & 1 == 0));
+    p.push(PescToken::Bool(!v.is_odd()));
     Ok(())
 }
 
@@ -598,17 +1044,627 @@ fn pesc_ex_abs(p: &mut Pesc) -> Result<(), PescErrorType> {
 }
 
 fn pesc_ex_coprime(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let v = p.pop_number()? as usize;
-    let u = p.pop_number()? as usize;
+    let v = p.pop_integer()?;
+    let u = p.pop_integer()?;
 
-    p.push(PescToken::Bool(gcd(u, v) == 1));
+    p.push(PescToken::Bool(gcd(&u, &v) == BigInt::one()));
     Ok(())
 }
 
 fn pesc_ex_prime(p: &mut Pesc) -> Result<(), PescErrorType> {
-    let x = p.pop_number()? as usize;
+    let x = p.pop_integer()?;
 
-    p.push(PescToken::Bool(is_prime(x)));
+    // negative numbers are never prime
+    let prime = !x.is_negative() && is_prime(as_usize(&x)?, || p.charge(1))?;
+    p.push(PescToken::Bool(prime));
+    Ok(())
+}
+
+// --- combinators ---
+
+fn pesc_ex_times(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let body = p.pop()?;
+    let n = as_usize(&p.pop_integer()?)?;
+
+    for _ in 0..n {
+        // an empty body doesn't take any steps to run, so count one here
+        p.charge(1)?;
+        p.try_exec(body.clone())?;
+    }
+
+    Ok(())
+}
+
+fn pesc_ex_while(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let body = p.pop()?;
+    let cond = p.pop()?;
+
+    while test(p, &cond)? {
+        p.try_exec(body.clone())?;
+    }
+
+    Ok(())
+}
+
+fn pesc_ex_dip(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let body = p.pop()?;
+    let x = p.pop()?;
+
+    p.try_exec(body)?;
+    p.push(x);
+    Ok(())
+}
+
+fn pesc_ex_linrec(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let after = p.pop()?;
+    let before = p.pop()?;
+    let then = p.pop()?;
+    let cond = p.pop()?;
+
+    // rather than actually recursing, run `before` until the condition
+    // is true, and then `after` once for each time `before` was run
+    let mut depth = 0_usize;
+    while !test(p, &cond)? {
+        p.try_exec(before.clone())?;
+        depth += 1;
+    }
+
+    p.try_exec(then)?;
+
+    for _ in 0..depth {
+        p.try_exec(after.clone())?;
+    }
+
+    Ok(())
+}
+
+fn pesc_ex_binrec(p: &mut Pesc) -> Result<(), PescErrorType> {
+    enum Task {
+        Recurse,
+        Push(PescToken),
+        Join,
+    }
+
+    let join = p.pop()?;
+    let split = p.pop()?;
+    let then = p.pop()?;
+    let cond = p.pop()?;
+
+    // keep our own list of things to do, rather than recursing, so
+    // that deep recursion doesn't overflow Rust's stack
+    let mut tasks = vec![Task::Recurse];
+
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Recurse if test(p, &cond)? => p.try_exec(then.clone())?,
+            Task::Recurse => {
+                // set aside the second half while the first is worked on
+                p.try_exec(split.clone())?;
+                let second = p.pop()?;
+                tasks.extend(vec![Task::Join, Task::Recurse,
+                    Task::Push(second), Task::Recurse]);
+            },
+            Task::Push(v) => p.push(v),
+            Task::Join => p.try_exec(join.clone())?,
+        }
+    }
+
+    Ok(())
+}
+
+fn pesc_ex_primrec(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let combine = p.pop()?;
+    let init = p.pop()?;
+
+    // numbers are counted down to 1, and sequences are split up
+    let count = match p.pop()? {
+        PescToken::Number(n) => {
+            let n = as_usize(&n.to_int().ok_or_else(||
+                PescErrorType::InvalidArgumentType(
+                    String::from("integer"), n.to_string()))?)?;
+            for i in (1..=n).rev() {
+                p.push_limited(PescToken::Number(PescNumber::from(i)))?;
+            }
+            n
+        },
+        v => {
+            let (items, _) = sequence(v)?;
+            let count = items.len();
+            for item in items {
+                p.push_limited(item)?;
+            }
+            count
+        },
+    };
+
+    p.try_exec(init)?;
+
+    for _ in 0..count {
+        p.try_exec(combine.clone())?;
+    }
+
+    Ok(())
+}
+
+fn pesc_ex_step(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let body = p.pop()?;
+    let (items, _) = sequence(p.pop()?)?;
+
+    for item in items {
+        p.push(item);
+        p.try_exec(body.clone())?;
+    }
+
+    Ok(())
+}
+
+fn pesc_ex_map(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let body = p.pop()?;
+    let (items, kind) = sequence(p.pop()?)?;
+
+    let mut mapped = Vec::with_capacity(items.len());
+    for item in items {
+        p.push(item);
+        let result = p.try_peek(body.clone())?;
+        nestable(&result)?;
+        mapped.push(result);
+        p.pop()?;
+    }
+
+    p.push(unsequence(mapped, kind)?);
+    Ok(())
+}
+
+fn pesc_ex_filter(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let cond = p.pop()?;
+    let (items, kind) = sequence(p.pop()?)?;
+
+    let mut kept = Vec::new();
+    for item in items {
+        p.push(item);
+        let keep = test(p, &cond)?;
+
+        let item = p.pop()?;
+        if keep {
+            kept.push(item);
+        }
+    }
+
+    p.push(unsequence(kept, kind)?);
+    Ok(())
+}
+
+fn pesc_ex_fold(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let body = p.pop()?;
+    let init = p.pop()?;
+    let (items, _) = sequence(p.pop()?)?;
+
+    p.push(init);
+    for item in items {
+        p.push(item);
+        p.try_exec(body.clone())?;
+    }
+
+    Ok(())
+}
+
+// --- list functions ---
+
+fn pesc_ex_list(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let n = p.pop_integer()?;
+    let n = as_index(&n, "stack", p.stack.len(), true)?;
+
+    let mut items = Vec::with_capacity(n);
+    for _ in 0..n {
+        let item = p.pop()?;
+        nestable(&item)?;
+        items.push(item);
+    }
+    items.reverse();
+
+    p.push(PescToken::List(items.into()));
+    Ok(())
+}
+
+fn pesc_ex_nth(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let i = p.pop_integer()?;
+    let mut list = p.pop_list()?;
+
+    let i = as_index(&i, "list", list.len(), false)?;
+    p.push(list.swap_remove(i));
+    Ok(())
+}
+
+fn pesc_ex_slice(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let end = p.pop_integer()?;
+    let start = p.pop_integer()?;
+    let mut list = p.pop_list()?;
+
+    // a start past the end gives an empty list
+    let end = as_index(&end, "list", list.len(), true)?;
+    let start = as_index(&start, "list", list.len(), true)?.min(end);
+
+    list.truncate(end);
+    p.push(PescToken::List(list.split_off(start).into()));
+    Ok(())
+}
+
+fn pesc_ex_len(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let len = match p.pop()? {
+        PescToken::List(l) => l.len(),
+        PescToken::Macro(m) => m.len(),
+        PescToken::Str(s) => s.chars().count(),
+        v => return Err(PescErrorType::InvalidArgumentType(
+            String::from("macro/list/string"), v.to_string())),
+    };
+
+    p.push(PescToken::Number(PescNumber::from(len)));
+    Ok(())
+}
+
+fn pesc_ex_concat(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let b = p.pop()?;
+    let (mut a, kind) = sequence(p.pop()?)?;
+
+    match sequence(b.clone()) {
+        Ok((b, k)) if k == kind => a.extend(b),
+        _ => return Err(PescErrorType::InvalidArgumentType(
+            seq_name(kind), b.to_string())),
+    }
+
+    p.push(unsequence(a, kind)?);
+    Ok(())
+}
+
+// split a sequence into its first value and the rest, failing if
+// it's empty.
+fn uncons(p: &mut Pesc) -> Result<(PescToken, PescToken), PescErrorType> {
+    let (mut items, kind) = sequence(p.pop()?)?;
+
+    if items.is_empty() {
+        return Err(PescErrorType::EmptySequence(seq_name(kind)));
+    }
+
+    let first = items.remove(0);
+    Ok((first, unsequence(items, kind)?))
+}
+
+fn pesc_ex_cons(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let (mut items, kind) = sequence(p.pop()?)?;
+    let x = p.pop()?;
+    nestable(&x)?;
+    items.insert(0, x);
+
+    p.push(unsequence(items, kind)?);
+    Ok(())
+}
+
+fn pesc_ex_uncons(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let (first, rest) = uncons(p)?;
+    p.push(first);
+    p.push(rest);
+    Ok(())
+}
+
+fn pesc_ex_first(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let (first, _) = uncons(p)?;
+    p.push(first);
+    Ok(())
+}
+
+fn pesc_ex_rest(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let (_, rest) = uncons(p)?;
+    p.push(rest);
+    Ok(())
+}
+
+fn pesc_ex_reverse(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let mut list = p.pop_list()?;
+    list.reverse();
+    p.push(PescToken::List(list.into()));
+    Ok(())
+}
+
+fn pesc_ex_explode(p: &mut Pesc) -> Result<(), PescErrorType> {
+    for item in p.pop_list()? {
+        p.push(item);
+    }
+
+    Ok(())
+}
+
+// --- map functions ---
+
+fn pesc_ex_at(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let key = p.pop_string()?;
+    let mut map = p.pop_map()?;
+
+    match map.remove(&key) {
+        Some(v) => {
+            p.push(v);
+            Ok(())
+        },
+        None => Err(PescErrorType::NoSuchKey(key)),
+    }
+}
+
+fn pesc_ex_put(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let value = p.pop()?;
+    nestable(&value)?;
+    let key = p.pop_string()?;
+    let mut map = p.pop_map()?;
+
+    map.insert(key, value);
+    p.push(PescToken::Map(map.into()));
+    Ok(())
+}
+
+fn pesc_ex_has(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let key = p.pop_string()?;
+    let map = p.pop_map()?;
+
+    p.push(PescToken::Bool(map.contains_key(&key)));
+    Ok(())
+}
+
+fn pesc_ex_keys(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let map = p.pop_map()?;
+    p.push(PescToken::List(map.into_keys().map(|k| PescToken::Str(k.into())).collect::<Vec<_>>().into()));
+    Ok(())
+}
+
+fn pesc_ex_values(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let map = p.pop_map()?;
+    p.push(PescToken::List(map.into_values().collect::<Vec<_>>().into()));
+    Ok(())
+}
+
+fn pesc_ex_del(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let key = p.pop_string()?;
+    let mut map = p.pop_map()?;
+
+    map.remove(&key);
+    p.push(PescToken::Map(map.into()));
+    Ok(())
+}
+
+// --- string functions ---
+
+fn pesc_ex_substr(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let end = p.pop_integer()?;
+    let start = p.pop_integer()?;
+    let chars = p.pop_string()?.chars().collect::<Vec<_>>();
+
+    // just like [slice], but indexing characters
+    let end = as_index(&end, "string", chars.len(), true)?;
+    let start = as_index(&start, "string", chars.len(), true)?.min(end);
+
+    p.push(PescToken::Str(chars[start..end].iter().collect::<String>().into()));
+    Ok(())
+}
+
+fn pesc_ex_split(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let sep = p.pop_string()?;
+    let s = p.pop_string()?;
+
+    let parts: Vec<_> = match sep.is_empty() {
+        true => s.chars().map(|c| PescToken::Str(c.to_string().into())).collect(),
+        false => s.split(sep.as_str()).map(|s| PescToken::Str(s.into())).collect(),
+    };
+
+    p.push(PescToken::List(parts.into()));
+    Ok(())
+}
+
+fn pesc_ex_join(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let sep = p.pop_string()?;
+    let mut parts = Vec::new();
+
+    for item in p.pop_list()? {
+        match item {
+            PescToken::Str(s) => parts.push(s),
+            _ => return Err(PescErrorType::InvalidArgumentType(
+                String::from("string"), item.to_string())),
+        }
+    }
+
+    p.push(PescToken::Str(parts.join(&sep).into()));
+    Ok(())
+}
+
+fn pesc_ex_trim(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let s = p.pop_string()?;
+    p.push(PescToken::Str(s.trim().to_string().into()));
+    Ok(())
+}
+
+fn pesc_ex_upper(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let s = p.pop_string()?;
+    p.push(PescToken::Str(s.to_uppercase().into()));
+    Ok(())
+}
+
+fn pesc_ex_lower(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let s = p.pop_string()?;
+    p.push(PescToken::Str(s.to_lowercase().into()));
+    Ok(())
+}
+
+fn pesc_ex_find(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let t = p.pop_string()?;
+    let s = p.pop_string()?;
+
+    // indices count characters, not bytes
+    let i = match s.find(&t) {
+        Some(b) => PescNumber::from(s[..b].chars().count()),
+        None => -PescNumber::from(1_usize),
+    };
+
+    p.push(PescToken::Number(i));
+    Ok(())
+}
+
+fn pesc_ex_replace(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let to = p.pop_string()?;
+    let from = p.pop_string()?;
+    let s = p.pop_string()?;
+
+    if from.is_empty() {
+        return Err(PescErrorType::InvalidArgumentType(
+            String::from("non-empty string"), pretty::string(&from)));
+    }
+
+    p.push(PescToken::Str(s.replace(&from, &to).into()));
+    Ok(())
+}
+
+fn pesc_ex_repeat(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let n = as_usize(&p.pop_integer()?)?;
+    let s = p.pop_string()?;
+
+    p.charge(n)?;
+    p.push(PescToken::Str(s.repeat(n).into()));
+    Ok(())
+}
+
+fn pesc_ex_str(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let x = p.pop_number()?;
+    p.push(PescToken::Str(x.to_string().into()));
+    Ok(())
+}
+
+fn pesc_ex_num(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let s = p.pop_string()?;
+
+    match PescNumber::parse_literal(s.trim()) {
+        Some(n) => p.push(PescToken::Number(n)),
+        None => return Err(PescErrorType::InvalidNumberLit(s)),
+    }
+
+    Ok(())
+}
+
+fn pesc_ex_fmt(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let f = p.pop_string()?;
+    let invalid = || PescErrorType::InvalidArgumentType(
+        String::from("format"), pretty::string(&f));
+
+    // a format is a number of decimal places (".2"), a style ("e" for
+    // scientific notation, or "x", "o", or "b" for hex, octal, or
+    // binary integers), or both (".2e")
+    let style = f.trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let places = match &f[..f.len() - style.len()] {
+        "" => None,
+        p => Some(p.strip_prefix('.').and_then(|d| d.parse::<usize>().ok())
+            .ok_or_else(invalid)?),
+    };
+
+    let s = match (places, style) {
+        (None, "") => p.pop_number()?.to_string(),
+        (Some(n), "") => match p.pop_real()? {
+            x @ PescNumber::Float(_) => format!("{:.*}", n.min(PESC_EX_FMT_FLOAT_PLACES), x),
+            x => format!("{:.*}", n.min(PESC_EX_FMT_EXACT_PLACES), x),
+        },
+        (None, "e") => format!("{:e}", p.pop_real()?.to_f64()),
+        (Some(n), "e") => format!("{:.*e}", n.min(PESC_EX_FMT_FLOAT_PLACES),
+            p.pop_real()?.to_f64()),
+        (None, "x") => format!("{:#x}", p.pop_integer()?),
+        (None, "o") => format!("{:#o}", p.pop_integer()?),
+        (None, "b") => format!("{:#b}", p.pop_integer()?),
+        _ => return Err(invalid()),
+    };
+
+    p.push(PescToken::Str(s.into()));
+    Ok(())
+}
+
+// --- help functions ---
+
+// the function called `name`, which may also be an operator.
+fn lookup(p: &Pesc, name: &str) -> Result<String, PescErrorType> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if let Some(f) = p.ops.get(&c) {
+            return Ok(f.clone());
+        }
+    }
+
+    match p.func(name) {
+        Some(_) => Ok(name.to_lowercase()),
+        None => Err(PescErrorType::UnknownFunction(name.to_string())),
+    }
+}
+
+// a function's name, followed by its operators, e.g. "mul (* ×)".
+fn with_ops(p: &Pesc, name: &str) -> String {
+    let mut ops = p.ops.iter()
+        .filter(|(_, f)| *f == name)
+        .map(|(o, _)| o.to_string())
+        .collect::<Vec<_>>();
+    ops.sort();
+
+    match ops.is_empty() {
+        true => name.to_string(),
+        false => format!("{} ({})", name, ops.join(" ")),
+    }
+}
+
+fn pesc_ex_help(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let name = p.pop_string()?;
+    let name = lookup(p, &name)?;
+
+    let text = match p.info(&name) {
+        Some(info) => format!("{}: {}\n    {}", with_ops(p, &name), info, info.doc),
+        None => format!("{}: defined with [def]; see its source with \"{}\" [see]",
+            with_ops(p, &name), name),
+    };
+
+    for line in text.lines() {
+        p.print(line);
+    }
+    Ok(())
+}
+
+fn pesc_ex_words(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let mut groups: BTreeMap<Option<Category>, Vec<String>> = BTreeMap::new();
+    for name in p.names() {
+        let category = p.info(name).map(|i| i.category);
+        groups.entry(category).or_default().push(with_ops(p, name));
+    }
+
+    // functions defined with [def] come after the rest
+    let defined = groups.remove(&None);
+    let lines = groups.into_iter()
+        .filter_map(|(c, names)| Some((c?.to_string(), names)))
+        .chain(defined.map(|names| (String::from("defined"), names)))
+        .map(|(c, names)| format!("{}: {}", c, names.join(", ")))
+        .collect::<Vec<_>>();
+
+    for line in lines {
+        p.print(&line);
+    }
+    Ok(())
+}
+
+fn pesc_ex_dbg(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let x = p.pop()?;
+    p.print(&pretty::token(&x));
+    p.push(x);
+    Ok(())
+}
+
+fn pesc_ex_see(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let name = p.pop_string()?;
+    let name = lookup(p, &name)?;
+
+    let line = match p.func(&name) {
+        Some(PescFuncBody::Macro(chunk)) =>
+            format!("{} {} [def]", pretty::token(&PescToken::Macro(chunk.code.clone().into())),
+                pretty::token(&PescToken::Str(name.clone().into()))),
+        _ => format!("{} is built in; see what it does with \"{}\" [help]", name, name),
+    };
+
+    p.print(&line);
     Ok(())
 }
 
@@ -616,5 +1672,36 @@ fn pesc_ex_prime(p: &mut Pesc) -> Result<(), PescErrorType> {
 
 fn pesc_run(p: &mut Pesc) -> Result<(), PescErrorType> {
     let f = p.pop()?;
-    p.try_exec(f)
+    p.schedule(f)
+}
+
+// parse the code in a string. where in the string a parse error
+// happened is lost, as the error is reported at the function call.
+fn parse(p: &mut Pesc) -> Result<Vec<PescToken>, PescErrorType> {
+    let s = p.pop_string()?;
+    Pesc::parse(&s).map(|(_, code)| code).map_err(|e| e.kind)
+}
+
+fn pesc_ex_parse(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let code = parse(p)?;
+    p.push(PescToken::Macro(code.into()));
+    Ok(())
+}
+
+fn pesc_ex_quote(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let m = p.pop_macro()?;
+    p.push(PescToken::Str(pretty::source(&m).into()));
+    Ok(())
+}
+
+fn pesc_ex_quote1(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let x = p.pop()?;
+    nestable(&x)?;
+    p.push(PescToken::Macro(vec![x].into()));
+    Ok(())
+}
+
+fn pesc_ex_unquote(p: &mut Pesc) -> Result<(), PescErrorType> {
+    let code = parse(p)?;
+    p.schedule(PescToken::Macro(code.into()))
 }
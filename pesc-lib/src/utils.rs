@@ -1,4 +1,6 @@
 use crate::errors::*;
+use crate::bigint::BigInt;
+use crate::number::MAX_EXACT_BITS;
 
 pub fn pi() -> f64 {
     // machin formula
@@ -20,7 +22,10 @@ pub fn e(e_iters: usize) -> f64 {
         match iters {
             0 => accm,
             _ => {
-                let naccm = 1_f64 / factorial(iters) as f64;
+                let naccm = match factorial(iters, || Ok::<(), ()>(())) {
+                    Ok(Some(f)) => 1_f64 / f.to_f64(),
+                    _ => 0_f64,
+                };
                 calc_e(iters - 1, accm + naccm)
             }
         }
@@ -29,96 +34,144 @@ pub fn e(e_iters: usize) -> f64 {
     1_f64 + calc_e(e_iters, 0_f64)
 }
 
-pub fn is_prime(x: usize) -> bool {
+#[allow(clippy::manual_is_multiple_of)]
+pub fn is_prime<E, F>(x: usize, mut step: F) -> Result<bool, E>
+where
+    F: FnMut() -> Result<(), E>
+{
     // stolen from this SO answer:
     // https://stackoverflow.com/a/26760082
+    //
+    // step() is called for each pair of divisors tried, as there may
+    // be billions of them.
 
     if x <= 3 && x > 1 {
         // both 2 and 3 are prime
-        true
+        Ok(true)
     } else if x % 2 == 0 || x % 3 == 0 {
-        false
+        Ok(false)
     } else {
         let mut i = 5;
         while i * i <= x {
+            step()?;
+
             if x % i == 0 || x % (i + 2) == 0 {
-                return false;
+                return Ok(false);
             }
 
             i += 6;
         }
 
-        true
+        Ok(true)
     }
 }
 
-pub fn ackermann(m: usize, n: usize) -> usize {
-    // TODO: iterative version; A(4, 2) overflows
+pub fn ackermann<E, F>(m: usize, n: usize, mut step: F) -> Result<usize, E>
+where
+    F: FnMut() -> Result<(), E>
+{
     //           ⎛
     //           ⎢  n + 1                    if m = 0
     // A(m, n) = ⎨  A(m - 1, 1)              if m > 0 and n = 0
     //           ⎢  A(m - 1, A(m, n - 1))    if m > 0 and n > 0
     //           ⎝
-    match (m, n) {
-        (0, n) => n + 1,
-        (m, 0) if m > 0 => ackermann(m - 1, 1),
-        (m, n) if m > 0 && n > 0 =>
-            ackermann(m - 1, ackermann(m, n - 1)),
-        _ => unreachable!()
+    //
+    // the pending values of m are kept on a stack, rather than
+    // recursing, so that A(4, 1) and friends don't overflow Rust's
+    // call stack. step() is called for each step of the calculation,
+    // so that the caller can give up early.
+    let mut ms = vec![m];
+    let mut n = n;
+
+    while let Some(m) = ms.pop() {
+        step()?;
+
+        if m == 0 {
+            n += 1;
+        } else if n == 0 {
+            ms.push(m - 1);
+            n = 1;
+        } else {
+            ms.push(m - 1);
+            ms.push(m);
+            n -= 1;
+        }
     }
+
+    Ok(n)
 }
 
-pub fn lcm(a: usize, b: usize) -> usize {
+pub fn lcm(a: &BigInt, b: &BigInt) -> BigInt {
     //              ⎛           ⎞
     //              ⎜    |a|    ⎟
     // lcm(a, b) =  ⎜ ───────── ⎟ × |b|
     //              ⎜ gcd(a, b) ⎟
     //              ⎝           ⎠
-    (a / gcd(a, b)) * b
+    let g = gcd(a, b);
+    if g.is_zero() {
+        return g;
+    }
+
+    let (q, _) = a.abs().divrem(&g).unwrap();
+    &q * &b.abs()
 }
 
 // Josef Stein's binary GCD algorithm
-pub fn gcd(mut u: usize, mut v: usize) -> usize {
+pub fn gcd(u: &BigInt, v: &BigInt) -> BigInt {
     use std::mem::swap;
 
+    let mut u = u.abs();
+    let mut v = v.abs();
+
     // gcd(0, v) == v, gcd(u, 0) == u
-    if u == 0 {
+    if u.is_zero() {
         return v;
-    } else if v == 0 {
-        return u
+    } else if v.is_zero() {
+        return u;
     }
 
-    // found the answer
-    if v == u {
-        return v;
-    }
+    // gcd(2u, 2v) = 2 × gcd(u, v)
+    let shift = u.trailing_zeros().min(v.trailing_zeros());
 
-    if (u & 1) == 0 {
-        if (v & 1) == 0 {
-            // gcd(2u, 2v) = gcd(u, v)
-            2 * gcd(u / 2, v / 2)
-        } else {
-            // gcd(2u, v) = gcd(u, v)
-            gcd(u / 2, v)
-        }
-    } else if (v & 1) == 0 {
+    // gcd(2u, v) = gcd(u, v)
+    u = u.shr(u.trailing_zeros());
+
+    loop {
         // gcd(u, 2v) = gcd(u, v)
-        gcd(u, v / 2)
-    } else {
+        v = v.shr(v.trailing_zeros());
+
         // gcd(u, v) = gcd(|u - v|, min(u, v))
-        if u < v {
+        if u > v {
             swap(&mut u, &mut v);
         }
 
-        gcd(u - v, v)
+        v = &v - &u;
+
+        // found the answer
+        if v.is_zero() {
+            return u.shl(shift);
+        }
     }
 }
 
-pub fn factorial(n: usize) -> usize {
-    match n {
-        1 => n,
-        _ => n * factorial(n - 1),
+// n!, or None if it's more than MAX_EXACT_BITS bits long. step() is
+// called for each multiplication.
+pub fn factorial<E, F>(n: usize, mut step: F) -> Result<Option<BigInt>, E>
+where
+    F: FnMut() -> Result<(), E>
+{
+    let mut acc = BigInt::one();
+
+    for i in 2..=n {
+        step()?;
+        acc = &acc * &BigInt::from(i);
+
+        if acc.bits() > MAX_EXACT_BITS {
+            return Ok(None);
+        }
     }
+
+    Ok(Some(acc))
 }
 
 #[inline]